@@ -19,6 +19,7 @@
 (define $immutable-pair-type-id ($new-type-id))
 (define $mutable-pair-type-id ($new-type-id))
 (define $symbol-type-id ($new-type-id))
+(define $vector-type-id ($new-type-id))
 (define $empty-list ($new-type-id))
 
 ;Quote is not available in stage1 so use false as a placeholder that is replaced in stage2.
@@ -36,6 +37,8 @@
 (define (zero? x) (= x 0))
 (define (positive? x) (> x 0))
 (define (negative? x) (< x 0))
+(define (even? x) (zero? (remainder x 2)))
+(define (odd? x) (not (even? x)))
 (define (abs x) (if (negative? x) (- x) x))
 
 (define ($mutable-pair? x)
@@ -75,9 +78,22 @@
                         ($force-set-cdr! partial-list-tail x)
                         partial-list-head))))))
 
+(define (vector? x) (and ($object? x) (eqv? ($object-type-id-get x) $vector-type-id)))
+;Compares two sequences of the same type element by element from index onward using same?.
+(define $sequence-equal? #f)
+(set! $sequence-equal? (lambda (x y len ref same? index)
+    (and (= (len x) (len y))
+        (or (= index (len x))
+            (and (same? (ref x index) (ref y index))
+                ($sequence-equal? x y len ref same? (+ index 1)))))))
 (define equal? #f)
 (set! equal? (lambda (x y)
     (cond
         ((eqv? x y))
         ((and (pair? x) (pair? y)) (and (equal? (car x) (car y)) (equal? (cdr x) (cdr y))))
+        ((and (string? x) (string? y)) ($sequence-equal? x y string-length string-ref eqv? 0))
+        ((and (vector? x) (vector? y))
+            ($sequence-equal? x y $object-field-count $object-field-get equal? 0))
+        ((and (bytevector? x) (bytevector? y))
+            ($sequence-equal? x y bytevector-length bytevector-u8-ref eqv? 0))
         (else #f))))
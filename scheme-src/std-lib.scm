@@ -29,7 +29,74 @@
         (error 'symbol->string "Not a symbol.")))
 
 (define (list . lst) lst)
+(define (identity x) x)
+;Gives a procedure taking any number of arguments that always returns x.
+(define (const x) (lambda args x))
+(define (caar x) (car (car x)))
+(define (cadr x) (car (cdr x)))
+(define (cdar x) (cdr (car x)))
+(define (cddr x) (cdr (cdr x)))
+(define (caaar x) (car (car (car x))))
+(define (caadr x) (car (car (cdr x))))
+(define (cadar x) (car (cdr (car x))))
+(define (caddr x) (car (cdr (cdr x))))
+(define (cdaar x) (cdr (car (car x))))
+(define (cdadr x) (cdr (car (cdr x))))
+(define (cddar x) (cdr (cdr (car x))))
+(define (cdddr x) (cdr (cdr (cdr x))))
+(define (caaaar x) (car (car (car (car x)))))
+(define (caaadr x) (car (car (car (cdr x)))))
+(define (caadar x) (car (car (cdr (car x)))))
+(define (caaddr x) (car (car (cdr (cdr x)))))
+(define (cadaar x) (car (cdr (car (car x)))))
+(define (cadadr x) (car (cdr (car (cdr x)))))
+(define (caddar x) (car (cdr (cdr (car x)))))
+(define (cadddr x) (car (cdr (cdr (cdr x)))))
+(define (cdaaar x) (cdr (car (car (car x)))))
+(define (cdaadr x) (cdr (car (car (cdr x)))))
+(define (cdadar x) (cdr (car (cdr (car x)))))
+(define (cdaddr x) (cdr (car (cdr (cdr x)))))
+(define (cddaar x) (cdr (cdr (car (car x)))))
+(define (cddadr x) (cdr (cdr (car (cdr x)))))
+(define (cdddar x) (cdr (cdr (cdr (car x)))))
+(define (cddddr x) (cdr (cdr (cdr (cdr x)))))
+(define ($list-tail name lst k)
+    (let tail ((rest lst) (i k))
+        (cond
+            ((zero? i) rest)
+            ((pair? rest) (tail (cdr rest) (- i 1)))
+            (else (error name "List is too short." lst k)))))
+(define (list-tail lst k) ($list-tail 'list-tail lst k))
+(define (list-ref lst k)
+    (let ((tail ($list-tail 'list-ref lst k)))
+        (if (pair? tail) (car tail) (error 'list-ref "List is too short." lst k))))
+(define (first lst) (list-ref lst 0))
+(define (second lst) (list-ref lst 1))
+(define (third lst) (list-ref lst 2))
+(define (fourth lst) (list-ref lst 3))
+(define (fifth lst) (list-ref lst 4))
+(define (sixth lst) (list-ref lst 5))
+(define (seventh lst) (list-ref lst 6))
+(define (eighth lst) (list-ref lst 7))
+(define (ninth lst) (list-ref lst 8))
+(define (tenth lst) (list-ref lst 9))
+;Counts the pairs of lst, or gives #f if it is circular. The hare moves two pairs for each
+;one the tortoise moves, so it laps the tortoise within one pass of any cycle.
+(define (length+ lst)
+    (let race ((tortoise lst) (hare lst) (n 0))
+        (cond
+            ((not (pair? hare)) n)
+            ((not (pair? (cdr hare))) (+ n 1))
+            (else
+                (let ((tortoise (cdr tortoise)) (hare (cddr hare)))
+                    (if (eq? tortoise hare) #f (race tortoise hare (+ n 2))))))))
+(define (length lst)
+    (let ((n (length+ lst)))
+        (if (and n (null? (list-tail lst n)))
+            n
+            (error 'length "Not a proper list." lst))))
 (define (max x . in-rest)
+    (if (not (number? x)) (error 'max "Not a number." x))
     (let max ((x x) (rest in-rest))
         (if (null? rest)
             x
@@ -38,6 +105,7 @@
                     (max y new-rest)
                     (max x new-rest))))))
 (define (min x . in-rest)
+    (if (not (number? x)) (error 'min "Not a number." x))
     (let min ((x x) (rest in-rest))
         (if (null? rest)
             x
@@ -62,6 +130,7 @@
             new_str)))
 (define (string-copy str)
     ($string-truncating-copy str (string-length str)))
+;The buffer doubles in size when full so the list is only walked once.
 (define (list->string lst)
     (if (null? lst)
         ""
@@ -71,47 +140,714 @@
                     (if (= (string-length built-string) index)
                         built-string
                         ($string-truncating-copy built-string index)))
+                ((not (pair? lst-head)) (error 'list->string "Not a proper list." lst))
+                ((not (char? (car lst-head)))
+                    (error 'list->string "Element is not a character." index (car lst-head)))
                 ((= index (string-length built-string))
                     (conv-list ($string-truncating-copy built-string (* 2 index)) index lst-head))
                 (else
                     (string-set! built-string index (car lst-head))
                     (conv-list built-string (+ 1 index) (cdr lst-head)))))))
-(define (number->string x)
-    (if (zero? x)
-        (string-copy "0")
-        (let to-string ((x x) (chars '()))
-            (if (zero? x)
-                (list->string chars)
-                (let* ((digits "0123456789") (digit (string-ref digits (remainder x 10))) (rest (quotient x 10)))
-                    (to-string rest (cons digit chars)))))))
-(define display #f)
-(set! display (lambda (x)
-    (cond
-        ((char? x) (write-char x))
-        ((null? x) (display "()"))
-        ((pair? x)
-            (display "(")
-            (display (car x))
-            (let display-contents ((list (cdr x)))
+(define (number->string x . radix-list)
+    (let ((radix (if (null? radix-list) 10 (car radix-list))))
+        (cond
+            ((not (or (= radix 2) (= radix 8) (= radix 10) (= radix 16)))
+                (error 'number->string "Radix must be 2, 8, 10, or 16." radix))
+            ((zero? x) (string-copy "0"))
+            (else
+                ;Digits are taken from the negative remainders when x is negative
+                ;so the most negative number does not overflow.
+                (let to-string ((rest x) (chars '()))
+                    (if (zero? rest)
+                        (list->string (if (negative? x) (cons (string-ref "-" 0) chars) chars))
+                        (let ((digit (string-ref "0123456789abcdef" (abs (remainder rest radix)))))
+                            (to-string (quotient rest radix) (cons digit chars)))))))))
+(define ($assert-vector name x) (if (not (vector? x)) (error name "Not a vector." x)))
+(define (vector . elements) (apply $make-object $vector-type-id elements))
+(define (list->vector lst) (apply vector lst))
+;Without a fill the elements are the unspecified value, which is #f.
+(define (make-vector k . fill-list)
+    (let ((fill (if (null? fill-list) (if #f #f) (car fill-list))) (factory ($make-list-factory #t)))
+        (let fill-elements ((index 0))
+            (if (< index k)
+                (begin
+                    ((car factory) fill)
+                    (fill-elements (+ index 1)))))
+        (list->vector ((cdr factory) '()))))
+(define (vector-length v) ($assert-vector 'vector-length v) ($object-field-count v))
+(define (vector-ref v k) ($assert-vector 'vector-ref v) ($object-field-get v k))
+(define (vector-set! v k x) ($assert-vector 'vector-set! v) ($object-field-set! v k x))
+;Returns the optional start and end arguments in rest as a pair, defaulting to the whole of a
+;sequence of length len.
+(define ($optional-range name len rest)
+    (let* ((start (if (null? rest) 0 (car rest)))
+            (end (if (or (null? rest) (null? (cdr rest))) len (car (cdr rest)))))
+        (if (and (<= 0 start) (<= start end) (<= end len))
+            (cons start end)
+            (error name "Invalid range." start end))))
+(define (vector->list v . range)
+    ($assert-vector 'vector->list v)
+    (let ((bounds ($optional-range 'vector->list (vector-length v) range)))
+        (let to-list ((index (cdr bounds)) (lst '()))
+            (if (= index (car bounds))
+                lst
+                (to-list (- index 1) (cons (vector-ref v (- index 1)) lst))))))
+(define (vector-copy v . range)
+    ($assert-vector 'vector-copy v)
+    (list->vector (apply vector->list v range)))
+;Copies back to front when the destination starts after the source so overlapping ranges
+;within one vector are not overwritten before they are read.
+(define (vector-copy! to at from . range)
+    ($assert-vector 'vector-copy! to)
+    ($assert-vector 'vector-copy! from)
+    (let* ((bounds ($optional-range 'vector-copy! (vector-length from) range))
+            (start (car bounds))
+            (size (- (cdr bounds) start)))
+        (if (or (negative? at) (> (+ at size) (vector-length to)))
+            (error 'vector-copy! "Destination is too small." at size))
+        (if (<= at start)
+            (let copy-forward ((index 0))
+                (if (< index size)
+                    (begin
+                        (vector-set! to (+ at index) (vector-ref from (+ start index)))
+                        (copy-forward (+ index 1)))))
+            (let copy-backward ((index size))
+                (if (positive? index)
+                    (begin
+                        (vector-set! to (+ at index -1) (vector-ref from (+ start index -1)))
+                        (copy-backward (- index 1))))))))
+;Output string ports keep a buffer that doubles in size when full and the count of characters
+;written so far, so writing n characters takes O(n) time.
+(define $string-output-port-type-id ($new-type-id))
+(define (open-output-string) ($make-object $string-output-port-type-id (make-string 16) 0))
+(define (output-port? x)
+    (and ($object? x) (eqv? ($object-type-id-get x) $string-output-port-type-id)))
+(define ($assert-output-port name x) (if (not (output-port? x)) (error name "Not an output port." x)))
+(define (get-output-string port)
+    ($assert-output-port 'get-output-string port)
+    ($string-truncating-copy ($object-field-get port 0) ($object-field-get port 1)))
+(define $write-char-stdout write-char)
+(define (write-char c . port-list)
+    (if (null? port-list)
+        ($write-char-stdout c)
+        (let ((port (car port-list)))
+            ($assert-output-port 'write-char port)
+            (if (not (char? c)) (error 'write-char "Not a character." c))
+            (let ((buffer ($object-field-get port 0)) (len ($object-field-get port 1)))
+                (if (= len (string-length buffer))
+                    (begin
+                        (set! buffer ($string-truncating-copy buffer (* 2 len)))
+                        ($object-field-set! port 0 buffer)))
+                (string-set! buffer len c)
+                ($object-field-set! port 1 (+ len 1))))))
+;Names write uses for characters, the same ones the reader accepts.
+(define $char-names
+    '((#\alarm . "alarm") (#\backspace . "backspace") (#\delete . "delete")
+        (#\escape . "escape") (#\newline . "newline") (#\null . "null")
+        (#\return . "return") (#\space . "space") (#\tab . "tab")))
+;Prints x the way write does if write? is set and the way display does otherwise.
+;Lists and vectors nested more than depth levels down are shown as ... and only their first
+;length elements are shown. Either limit can be #f to leave it off.
+(define $print #f)
+(set! $print (lambda (x write? depth length . port-list)
+    (let ((put (lambda (str) (apply $print str #f #f #f port-list)))
+            (put-char (lambda (c) (apply write-char c port-list)))
+            (show (lambda (y) (apply $print y write? (and depth (- depth 1)) length port-list)))
+            (past-length? (lambda (count) (and length (>= count length)))))
+        (cond
+            ((and write? (char? x))
+                (put "#\\")
+                (let find-name ((names $char-names))
+                    (cond
+                        ((pair? names)
+                            (if (eqv? x (car (car names)))
+                                (put (cdr (car names)))
+                                (find-name (cdr names))))
+                        ;Other control characters are written in hex.
+                        ((or (< (char->integer x) 32) (<= 127 (char->integer x) 159))
+                            (put "x")
+                            (put (number->string (char->integer x) 16)))
+                        (else (put-char x)))))
+            ((char? x) (put-char x))
+            ((null? x) (put "()"))
+            ((and depth (<= depth 0) (or (pair? x) (vector? x))) (put "..."))
+            ((pair? x)
+                (put "(")
+                (let display-contents ((list x) (count 0))
+                    (cond
+                        ((null? list))
+                        ((past-length? count)
+                            (if (positive? count) (put " "))
+                            (put "..."))
+                        ((pair? list)
+                            (if (positive? count) (put " "))
+                            (show (car list))
+                            (display-contents (cdr list) (+ count 1)))
+                        (else
+                            (put " . ")
+                            (show list))))
+                (put ")"))
+            ((vector? x)
+                (put "#")
+                (apply $print (vector->list x) write? depth length port-list))
+            ((bytevector? x)
+                (put "#u8(")
+                (let display-bytes ((index 0))
+                    (if (< index (bytevector-length x))
+                        (begin
+                            (if (positive? index) (put " "))
+                            (if (past-length? index)
+                                (put "...")
+                                (begin
+                                    (put (number->string (bytevector-u8-ref x index)))
+                                    (display-bytes (+ index 1)))))))
+                (put ")"))
+            ((string? x)
+                (if write? (put-char #\"))
+                (let print-str ((index 0))
+                    (if (< index (string-length x))
+                        (let ((c (string-ref x index)))
+                            (cond
+                                ((not write?) (put-char c))
+                                ((eqv? c #\") (put "\\\""))
+                                ((eqv? c #\\) (put "\\\\"))
+                                ((eqv? c #\newline) (put "\\n"))
+                                ((eqv? c #\tab) (put "\\t"))
+                                ((eqv? c #\return) (put "\\r"))
+                                (else (put-char c)))
+                            (print-str (+ 1 index)))))
+                (if write? (put-char #\")))
+            ((number? x) (put (number->string x)))
+            ((boolean? x) (if x (put "#t") (put "#f")))
+            ((symbol? x) (put (symbol->string x)))
+            ((procedure? x)
+                (let ((name ($procedure-name x)))
+                    (if name
+                        (begin
+                            (put "#<procedure ")
+                            (put name)
+                            (put ">"))
+                        (put "#<procedure>"))))
+            (else (put "#Unwriteable_object"))))))
+(define (display-limited x depth length . port-list) (apply $print x #f depth length port-list))
+(define (display x . port-list) (apply $print x #f #f #f port-list))
+;Strings and characters are written the way the reader reads them.
+(define (write x . port-list) (apply $print x #t #f #f port-list))
+(define (newline . port-list) (apply display $newline-str port-list))
+;Lists that do not fit on the rest of the line within width columns are split with one element
+;per line, indented one column past their opening parenthesis. The port may be left out when
+;giving a width.
+(define (pretty-print x . rest)
+    (let* ((port-list (if (or (null? rest) (number? (car rest))) '() (list (car rest))))
+            (width-list (if (null? port-list) rest (cdr rest)))
+            (width (if (null? width-list) 80 (car width-list))))
+        (let print ((x x) (column 0))
+            (let ((flat (let ((port (open-output-string)))
+                    (write x port)
+                    (get-output-string port))))
                 (cond
-                    ((null? list))
-                    ((pair? list)
-                        (display " ")
-                        (display (car list))
-                        (display-contents (cdr list)))
+                    ((not (pair? x)) (apply write x port-list))
+                    ((<= (+ column (string-length flat)) width) (apply display flat port-list))
                     (else
-                        (display " . ")
-                        (display list))))
-            (display ")"))
-        ((string? x) (let print-str ((index 0))
-            (if (= (string-length x) index)
-                (if #f #f)
+                        (let ((indent (make-string (+ column 1) #\space)))
+                            (apply display "(" port-list)
+                            (print (car x) (+ column 1))
+                            (let print-contents ((list (cdr x)))
+                                (cond
+                                    ((null? list))
+                                    ((pair? list)
+                                        (apply newline port-list)
+                                        (apply display indent port-list)
+                                        (print (car list) (+ column 1))
+                                        (print-contents (cdr list)))
+                                    (else
+                                        (apply newline port-list)
+                                        (apply display indent port-list)
+                                        (apply display ". " port-list)
+                                        (print list (+ column 3)))))
+                            (apply display ")" port-list))))))
+        (apply newline port-list)))
+(define (assoc x lst)
+    (let find-pair ((lst lst))
+        (cond
+            ((null? lst) #f)
+            ((equal? x (car (car lst))) (car lst))
+            (else (find-pair (cdr lst))))))
+(define ($map-1 proc lst)
+    (let ((factory ($make-list-factory #t)))
+        (let map-list ((lst lst))
+            (if (pair? lst)
                 (begin
-                  (write-char (string-ref x index))
-                  (print-str (+ 1 index))))))
-        ((number? x) (display (number->string x)))
-        ((boolean? x) (if x (display "#t") (display "#f")))
-        ((symbol? x) (display (symbol->string x)))
-        (else (display "#Unwriteable_object")))))
-(define (newline) (display $newline-str))
-
+                    ((car factory) (proc (car lst)))
+                    (map-list (cdr lst)))
+                ((cdr factory) '())))))
+(define ($all-pairs? lists)
+    (let check ((lists lists))
+        (or (null? lists) (and (pair? (car lists)) (check (cdr lists))))))
+;Stops at the end of the shortest list.
+(define (map proc first . rest)
+    (if (null? rest)
+        ($map-1 proc first)
+        (let ((factory ($make-list-factory #t)))
+            (let map-lists ((lists (cons first rest)))
+                (if ($all-pairs? lists)
+                    (begin
+                        ((car factory) (apply proc ($map-1 car lists)))
+                        (map-lists ($map-1 cdr lists)))
+                    ((cdr factory) '()))))))
+;Both stop at the end of the shortest string.
+(define (string-map proc first . rest)
+    (let* ((strings (cons first rest))
+            (len (apply min ($map-1 string-length strings)))
+            (new-str (make-string len)))
+        (let build ((index 0))
+            (if (< index len)
+                (let ((c (apply proc ($map-1 (lambda (str) (string-ref str index)) strings))))
+                    (if (not (char? c)) (error 'string-map "Not a character." c))
+                    (string-set! new-str index c)
+                    (build (+ index 1)))))
+        new-str))
+(define (string-for-each proc first . rest)
+    (let* ((strings (cons first rest))
+            (len (apply min ($map-1 string-length strings))))
+        (let for-each-char ((index 0))
+            (if (< index len)
+                (begin
+                    (apply proc ($map-1 (lambda (str) (string-ref str index)) strings))
+                    (for-each-char (+ index 1)))))))
+;The last list is shared with the result instead of being copied.
+(define (append . lists)
+    (let append-lists ((lists lists))
+        (cond
+            ((null? lists) '())
+            ((null? (cdr lists)) (car lists))
+            (else
+                (let ((factory ($make-list-factory #t)))
+                    (let copy ((lst (car lists)))
+                        (cond
+                            ((pair? lst)
+                                ((car factory) (car lst))
+                                (copy (cdr lst)))
+                            ((not (null? lst)) (error 'append "Not a proper list." lst))))
+                    ((cdr factory) (append-lists (cdr lists))))))))
+;Like list, except the last argument is the tail instead of an element.
+(define (cons* first . rest)
+    (let ((factory ($make-list-factory #t)))
+        (let push ((x first) (rest rest))
+            (if (pair? rest)
+                (begin
+                    ((car factory) x)
+                    (push (car rest) (cdr rest)))
+                ((cdr factory) x)))))
+(define list* cons*)
+(define (append-map proc . lists)
+    (apply append (apply map proc lists)))
+(define (flatten lst)
+    (let ((factory ($make-list-factory #t)))
+        (let flatten-into ((x lst))
+            (cond
+                ((null? x))
+                ((pair? x)
+                    (flatten-into (car x))
+                    (flatten-into (cdr x)))
+                (else ((car factory) x))))
+        ((cdr factory) '())))
+(define (count pred lst)
+    (let count-list ((lst lst) (n 0))
+        (if (pair? lst)
+            (count-list (cdr lst) (if (pred (car lst)) (+ n 1) n))
+            n)))
+(define (find pred lst)
+    (let find-list ((lst lst))
+        (cond
+            ((not (pair? lst)) #f)
+            ((pred (car lst)) (car lst))
+            (else (find-list (cdr lst))))))
+;Stops at the end of the shortest list.
+(define (list-index pred first . rest)
+    (let index-lists ((lists (cons first rest)) (index 0))
+        (cond
+            ((not ($all-pairs? lists)) #f)
+            ((apply pred ($map-1 car lists)) index)
+            (else (index-lists ($map-1 cdr lists) (+ index 1))))))
+;Stops at the first true result of pred, which is returned.
+(define (any pred first . rest)
+    (let any-lists ((lists (cons first rest)))
+        (and ($all-pairs? lists)
+            (or (apply pred ($map-1 car lists)) (any-lists ($map-1 cdr lists))))))
+;Stops at the first false result of pred. Otherwise returns the last result, or #t for no elements.
+(define (every pred first . rest)
+    (let every-lists ((lists (cons first rest)) (last #t))
+        (if ($all-pairs? lists)
+            (let ((result (apply pred ($map-1 car lists))))
+                (and result (every-lists ($map-1 cdr lists) result)))
+            last)))
+;Like every and any, but always give #t or #f.
+(define (and-map pred first . rest) (if (apply every pred first rest) #t #f))
+(define (or-map pred first . rest) (if (apply any pred first rest) #t #f))
+(define (delete x lst . same-list)
+    (let ((same? (if (null? same-list) equal? (car same-list)))
+            (factory ($make-list-factory #t)))
+        (let delete-list ((lst lst))
+            (if (pair? lst)
+                (begin
+                    (if (not (same? x (car lst))) ((car factory) (car lst)))
+                    (delete-list (cdr lst)))
+                ((cdr factory) '())))))
+;Keeps the first of each group of duplicates.
+(define (delete-duplicates lst . same-list)
+    (let ((same? (if (null? same-list) equal? (car same-list)))
+            (factory ($make-list-factory #t)))
+        (let delete-list ((lst lst) (kept '()))
+            (cond
+                ((not (pair? lst)) ((cdr factory) '()))
+                ((list-index (lambda (y) (same? y (car lst))) kept) (delete-list (cdr lst) kept))
+                (else
+                    ((car factory) (car lst))
+                    (delete-list (cdr lst) (cons (car lst) kept)))))))
+;Stable merge sort giving a fresh list; elements that compare equal keep their order.
+(define (sort less? lst)
+    (define (merge a b)
+        (let ((factory ($make-list-factory #t)))
+            (let merge-lists ((a a) (b b))
+                (cond
+                    ((null? a) ((cdr factory) b))
+                    ((null? b) ((cdr factory) a))
+                    ((less? (car b) (car a)) ((car factory) (car b)) (merge-lists a (cdr b)))
+                    (else ((car factory) (car a)) (merge-lists (cdr a) b))))))
+    (define (sort-prefix lst n)
+        (if (> n 1)
+            (let ((half (quotient n 2)))
+                (merge (sort-prefix lst half) (sort-prefix (list-tail lst half) (- n half))))
+            (if (= n 1) (list (car lst)) '())))
+    (sort-prefix lst (length lst)))
+(define (vector-sort! v less?)
+    (let copy ((sorted (sort less? (vector->list v))) (index 0))
+        (if (pair? sorted)
+            (begin
+                (vector-set! v index (car sorted))
+                (copy (cdr sorted) (+ index 1))))))
+;cmp gets an element and the key, giving negative, zero or positive as the element is less than,
+;equal to or greater than the key.
+(define (vector-binary-search v key cmp)
+    (let search ((low 0) (high (vector-length v)))
+        (if (< low high)
+            (let* ((middle (quotient (+ low high) 2))
+                    (order (cmp (vector-ref v middle) key)))
+                (cond
+                    ((negative? order) (search (+ middle 1) high))
+                    ((positive? order) (search low middle))
+                    (else middle)))
+            #f)))
+;Every number is an exact integer until the numeric tower grows.
+(define (complex? x) (number? x))
+(define (real? x) (number? x))
+(define (rational? x) (number? x))
+(define (integer? x) (number? x))
+(define (exact-integer? x) (number? x))
+(define (exact? x) (if (number? x) #t (error 'exact? "Not a number." x)))
+(define (inexact? x) (if (number? x) #f (error 'inexact? "Not a number." x)))
+(define (square x) (* x x))
+;Exponentiation by squaring so large powers only take a logarithmic number of steps.
+(define (expt base power)
+    (if (negative? power)
+        (error 'expt "Negative powers are not supported." power)
+        (let expt-loop ((base base) (power power) (acc 1))
+            (cond
+                ((zero? power) acc)
+                ((even? power) (expt-loop (square base) (quotient power 2) acc))
+                (else (expt-loop base (- power 1) (* acc base)))))))
+(define $eof-object ($new-type-id))
+(define (eof-object) $eof-object)
+(define (eof-object? x) (eqv? x $eof-object))
+;String ports hold a copy of their string and the index of the next character.
+(define $string-input-port-type-id ($new-type-id))
+(define (open-input-string str)
+    (if (string? str)
+        ($make-object $string-input-port-type-id (string-copy str) 0)
+        (error 'open-input-string "Not a string." str)))
+(define (input-port? x)
+    (and ($object? x) (eqv? ($object-type-id-get x) $string-input-port-type-id)))
+(define ($assert-input-port name x) (if (not (input-port? x)) (error name "Not an input port." x)))
+(define (peek-char port)
+    ($assert-input-port 'peek-char port)
+    (let ((str ($object-field-get port 0)) (index ($object-field-get port 1)))
+        (if (= index (string-length str))
+            (eof-object)
+            (string-ref str index))))
+(define (read-char port)
+    ($assert-input-port 'read-char port)
+    (let ((c (peek-char port)))
+        (if (not (eof-object? c))
+            ($object-field-set! port 1 (+ ($object-field-get port 1) 1)))
+        c))
+;The newline is consumed but not included in the returned string.
+(define (read-line port)
+    ($assert-input-port 'read-line port)
+    (if (eof-object? (peek-char port))
+        (eof-object)
+        (let ((factory ($make-list-factory #t)))
+            (let read-chars ((c (read-char port)))
+                (if (not (or (eof-object? c) (eqv? c #\newline)))
+                    (begin
+                        ((car factory) c)
+                        (read-chars (read-char port)))))
+            (list->string ((cdr factory) '())))))
+(define (string->vector str . range)
+    (let* ((bounds ($optional-range 'string->vector (string-length str) range))
+            (v (make-vector (- (cdr bounds) (car bounds)))))
+        (let copy ((index (car bounds)))
+            (if (< index (cdr bounds))
+                (begin
+                    (vector-set! v (- index (car bounds)) (string-ref str index))
+                    (copy (+ index 1)))))
+        v))
+(define (vector->string v . range)
+    ($assert-vector 'vector->string v)
+    (let* ((bounds ($optional-range 'vector->string (vector-length v) range))
+            (str (make-string (- (cdr bounds) (car bounds)))))
+        (let copy ((index (car bounds)))
+            (if (< index (cdr bounds))
+                (let ((c (vector-ref v index)))
+                    (if (not (char? c))
+                        (error 'vector->string "Not a character." c))
+                    (string-set! str (- index (car bounds)) c)
+                    (copy (+ index 1)))))
+        str))
+(define (char-ci=? x y) (eqv? (char-foldcase x) (char-foldcase y)))
+;Negative, zero or positive as x sorts before, with or after y.
+(define ($string-compare x y fold)
+    (let compare ((index 0))
+        (cond
+            ((= index (string-length x)) (if (= index (string-length y)) 0 -1))
+            ((= index (string-length y)) 1)
+            (else
+                (let ((difference (- (char->integer (fold (string-ref x index)))
+                            (char->integer (fold (string-ref y index))))))
+                    (if (zero? difference) (compare (+ index 1)) difference))))))
+(define ($string-chain test? fold x y rest)
+    (let chain ((x x) (y y) (rest rest))
+        (and (test? ($string-compare x y fold))
+            (or (null? rest) (chain y (car rest) (cdr rest))))))
+(define (string=? x y . rest) ($string-chain zero? identity x y rest))
+(define (string<? x y . rest) ($string-chain negative? identity x y rest))
+(define (string>? x y . rest) ($string-chain positive? identity x y rest))
+(define (string<=? x y . rest) ($string-chain (lambda (n) (<= n 0)) identity x y rest))
+(define (string>=? x y . rest) ($string-chain (lambda (n) (>= n 0)) identity x y rest))
+(define (string-ci=? x y . rest) ($string-chain zero? char-foldcase x y rest))
+(define (string-ci<? x y . rest) ($string-chain negative? char-foldcase x y rest))
+(define (string-ci>? x y . rest) ($string-chain positive? char-foldcase x y rest))
+(define (string-ci<=? x y . rest) ($string-chain (lambda (n) (<= n 0)) char-foldcase x y rest))
+(define (string-ci>=? x y . rest) ($string-chain (lambda (n) (>= n 0)) char-foldcase x y rest))
+(define (error-object? x)
+    (and ($object? x) (eqv? ($object-type-id-get x) $error-object-type-id)))
+(define ($assert-error-object name x)
+    (if (not (error-object? x)) (error name "Not an error object." x)))
+(define (error-object-message x)
+    ($assert-error-object 'error-object-message x)
+    ($object-field-get x 1))
+(define (error-object-irritants x)
+    ($assert-error-object 'error-object-irritants x)
+    ($object-field-get x 2))
+;Describes a raised object that nothing caught.
+(define ($condition->string condition)
+    (let ((port (open-output-string)))
+        (if (error-object? condition)
+            (let ((who ($object-field-get condition 0)))
+                (if who
+                    (begin
+                        (display who port)
+                        (display ": " port)))
+                (display (error-object-message condition) port)
+                (let write-irritants ((irritants (error-object-irritants condition)))
+                    (if (pair? irritants)
+                        (begin
+                            (display " " port)
+                            (write (car irritants) port)
+                            (write-irritants (cdr irritants))))))
+            (begin
+                (display "Uncaught raise of " port)
+                (write condition port)
+                (display "." port)))
+        (get-output-string port)))
+(define (string-null? str) (zero? (string-length str)))
+;Start and end may be equal, giving the empty string.
+(define (substring str start end)
+    (let* ((bounds ($optional-range 'substring (string-length str) (list start end)))
+            (start (car bounds))
+            (end (cdr bounds))
+            (new-str (make-string (- end start))))
+        (let copy ((index start))
+            (if (< index end)
+                (begin
+                    (string-set! new-str (- index start) (string-ref str index))
+                    (copy (+ index 1)))))
+        new-str))
+;Hash tables keep a vector of buckets, each an association list, along with the equivalence
+;predicate, the hash function and the number of entries. The buckets double when there are
+;more entries than buckets.
+(define $hash-table-type-id ($new-type-id))
+(define (make-hash-table . args)
+    (let* ((same? (if (null? args) equal? (car args)))
+            (hash (cond
+                ((and (pair? args) (pair? (cdr args))) (car (cdr args)))
+                ((or (eq? same? eq?) (eq? same? eqv?)) eqv-hash)
+                (else equal-hash))))
+        ($make-object $hash-table-type-id same? hash (make-vector 8 '()) 0)))
+(define (hash-table? x)
+    (and ($object? x) (eqv? ($object-type-id-get x) $hash-table-type-id)))
+(define ($assert-hash-table name x) (if (not (hash-table? x)) (error name "Not a hash table." x)))
+(define ($hash-table-index table key buckets)
+    (abs (remainder (($object-field-get table 1) key) (vector-length buckets))))
+;Returns the pair holding key and its value, or #f if key is not in the table.
+(define ($hash-table-entry table key)
+    (let* ((same? ($object-field-get table 0))
+            (buckets ($object-field-get table 2)))
+        (let find-entry ((bucket (vector-ref buckets ($hash-table-index table key buckets))))
+            (cond
+                ((null? bucket) #f)
+                ((same? key (car (car bucket))) (car bucket))
+                (else (find-entry (cdr bucket)))))))
+(define ($hash-table-add-entry! table entry buckets)
+    (let ((index ($hash-table-index table (car entry) buckets)))
+        (vector-set! buckets index (cons entry (vector-ref buckets index)))))
+(define ($hash-table-for-each-entry proc table)
+    (let ((buckets ($object-field-get table 2)))
+        (let walk-buckets ((index 0))
+            (if (< index (vector-length buckets))
+                (let walk-bucket ((bucket (vector-ref buckets index)))
+                    (if (null? bucket)
+                        (walk-buckets (+ index 1))
+                        (begin
+                            (proc (car bucket))
+                            (walk-bucket (cdr bucket)))))))))
+(define ($hash-table-grow! table)
+    (let ((new-buckets (make-vector (* 2 (vector-length ($object-field-get table 2))) '())))
+        ($hash-table-for-each-entry
+            (lambda (entry) ($hash-table-add-entry! table entry new-buckets))
+            table)
+        ($object-field-set! table 2 new-buckets)))
+(define (hash-table-set! table key value)
+    ($assert-hash-table 'hash-table-set! table)
+    (let ((entry ($hash-table-entry table key)) (count ($object-field-get table 3)))
+        (if entry
+            (set-cdr! entry value)
+            (begin
+                (if (= count (vector-length ($object-field-get table 2)))
+                    ($hash-table-grow! table))
+                ($hash-table-add-entry! table (cons key value) ($object-field-get table 2))
+                ($object-field-set! table 3 (+ count 1))))))
+;Calls fail if it is given and key is not in the table.
+(define (hash-table-ref table key . fail-list)
+    ($assert-hash-table 'hash-table-ref table)
+    (let ((entry ($hash-table-entry table key)))
+        (cond
+            (entry (cdr entry))
+            ((pair? fail-list) ((car fail-list)))
+            (else (error 'hash-table-ref "Key not found." key)))))
+(define (hash-table-ref/default table key default)
+    (hash-table-ref table key (lambda () default)))
+;Stores the result of updater on the current value, calling fail for one if key is not there.
+(define (hash-table-update! table key updater . fail-list)
+    ($assert-hash-table 'hash-table-update! table)
+    (hash-table-set! table key (updater (apply hash-table-ref table key fail-list))))
+(define (hash-table-update!/default table key updater default)
+    (hash-table-update! table key updater (lambda () default)))
+(define (hash-table-contains? table key)
+    ($assert-hash-table 'hash-table-contains? table)
+    (if ($hash-table-entry table key) #t #f))
+;Wraps proc so it is only called once for each list of arguments that are equal?
+;Cached results are kept in a hash table keyed on the argument list.
+(define (memoize proc)
+    (let ((cache (make-hash-table equal?)))
+        (lambda args
+            (hash-table-ref cache args
+                (lambda ()
+                    (let ((result (apply proc args)))
+                        (hash-table-set! cache args result)
+                        result))))))
+(define (hash-table-delete! table key)
+    ($assert-hash-table 'hash-table-delete! table)
+    (let* ((entry ($hash-table-entry table key))
+            (buckets ($object-field-get table 2))
+            (index ($hash-table-index table key buckets)))
+        (if entry
+            (begin
+                (vector-set! buckets index
+                    (let remove ((bucket (vector-ref buckets index)))
+                        (if (eq? (car bucket) entry)
+                            (cdr bucket)
+                            (cons (car bucket) (remove (cdr bucket))))))
+                ($object-field-set! table 3 (- ($object-field-get table 3) 1))))))
+(define (hash-table-count table)
+    ($assert-hash-table 'hash-table-count table)
+    ($object-field-get table 3))
+;The order of entries is unspecified.
+(define (hash-table->alist table)
+    ($assert-hash-table 'hash-table->alist table)
+    (let ((factory ($make-list-factory #t)))
+        ($hash-table-for-each-entry
+            (lambda (entry) ((car factory) (cons (car entry) (cdr entry))))
+            table)
+        ((cdr factory) '())))
+;Takes the same optional arguments as make-hash-table. Earlier pairs win, as they do for assoc.
+(define (alist->hash-table alist . args)
+    (let ((table (apply make-hash-table args)))
+        (let add-pairs ((alist alist))
+            (if (pair? alist)
+                (let ((key (car (car alist))))
+                    (if (not (hash-table-contains? table key))
+                        (hash-table-set! table key (cdr (car alist))))
+                    (add-pairs (cdr alist)))))
+        table))
+(define (hash-table-keys table) (map car (hash-table->alist table)))
+(define (hash-table-values table) (map cdr (hash-table->alist table)))
+;Anything but exactly one value is wrapped in an object holding the list of values, which
+;call-with-values unwraps.
+(define $values-type-id ($new-type-id))
+(define (values . things)
+    (if (and (pair? things) (null? (cdr things)))
+        (car things)
+        ($make-object $values-type-id things)))
+(define (call-with-values producer consumer)
+    (let ((result (producer)))
+        (if (and ($object? result) (eqv? ($object-type-id-get result) $values-type-id))
+            (apply consumer ($object-field-get result 0))
+            (consumer result))))
+(define (list->values lst) (apply values lst))
+;Since multiple values are a single object, this can take them as a normal argument.
+(define (values->list x) (call-with-values (lambda () x) list))
+;A promise holds a pair of whether it is done and either its value or a thunk computing it.
+(define (promise? x) (and ($object? x) (eqv? ($object-type-id-get x) $promise-type-id)))
+(define (make-promise x) (if (promise? x) x ($make-promise #t x)))
+;The thunk of a delay-force returns another promise, whose state this promise then takes over
+;and shares. Forcing a chain of them loops here rather than nesting, so it runs in constant space.
+(define (force promise)
+    (let force-loop ()
+        (if (not (promise? promise))
+            promise
+            (let ((state ($object-field-get promise 0)))
+                (if (car state)
+                    (cdr state)
+                    (let ((next ((cdr state))))
+                        (if (not (promise? next))
+                            (error 'force "delay-force did not give a promise." next))
+                        ;Forcing the thunk may have forced this promise too.
+                        (if (not (car state))
+                            (let ((next-state ($object-field-get next 0)))
+                                (set-car! state (car next-state))
+                                (set-cdr! state (cdr next-state))
+                                ($object-field-set! next 0 state)))
+                        (force-loop)))))))
+;Streams are pairs whose cdr is a promise of the rest of the stream, as made by cons-stream.
+(define the-empty-stream '())
+(define (stream-null? x) (null? x))
+(define (stream-pair? x) (and (pair? x) (promise? (cdr x))))
+(define ($assert-stream-pair name x) (if (not (stream-pair? x)) (error name "Not a stream pair." x)))
+(define (stream-car s) ($assert-stream-pair 'stream-car s) (car s))
+(define (stream-cdr s) ($assert-stream-pair 'stream-cdr s) (force (cdr s)))
+(define (stream-ref s k)
+    (let walk ((s s) (k k))
+        (if (zero? k)
+            (stream-car s)
+            (walk (stream-cdr s) (- k 1)))))
@@ -20,11 +20,14 @@
 use std::sync::atomic::{AtomicU64, Ordering};
 
 use AstNodeInner::*;
-use AstNodeNonList::{Bool, Number, String as SchemeString, Symbol};
+use AstNodeNonList::{Bool, Bytevector, Char, Number, String as SchemeString, Symbol, Vector};
 
 use crate::environment;
 use crate::types::*;
 
+#[cfg(test)]
+mod test;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum CoreSymbol {
     And,
@@ -32,14 +35,24 @@ pub enum CoreSymbol {
     Or,
     Let,
     LetRec,
+    LetRecStar,
     LetStar,
     Lambda,
+    NamedLambda,
     If,
     Set,
     Error,
     Quote,
     BeginProgram,
     GenUnspecified,
+    Cond,
+    Raise,
+    Guard,
+    UnwindProtect,
+    Add,
+    Less,
+    MakePromise,
+    Cons,
 }
 
 impl CoreSymbol {
@@ -50,14 +63,24 @@ impl CoreSymbol {
             CoreSymbol::Or => "or",
             CoreSymbol::Let => "let",
             CoreSymbol::LetRec => "letrec",
+            CoreSymbol::LetRecStar => "letrec*",
             CoreSymbol::LetStar => "let*",
             CoreSymbol::Lambda => "lambda",
+            CoreSymbol::NamedLambda => "$named-lambda",
             CoreSymbol::If => "if",
             CoreSymbol::Set => "set",
             CoreSymbol::Error => "error",
             CoreSymbol::Quote => "quote",
             CoreSymbol::BeginProgram => "$begin-program",
             CoreSymbol::GenUnspecified => "$gen_unspecified",
+            CoreSymbol::Cond => "cond",
+            CoreSymbol::Raise => "raise",
+            CoreSymbol::Guard => "$guard",
+            CoreSymbol::UnwindProtect => "$unwind-protect",
+            CoreSymbol::Add => "+",
+            CoreSymbol::Less => "<",
+            CoreSymbol::MakePromise => "$make-promise",
+            CoreSymbol::Cons => "cons",
         }
     }
 }
@@ -232,6 +255,9 @@ enum AstNodeNonList {
     Symbol(AstSymbol),
     String(String),
     Bool(bool),
+    Char(char),
+    Vector(Vec<AstNode>),
+    Bytevector(Vec<u8>),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -260,11 +286,23 @@ impl AstNode {
         Self::from_non_list(Bool(boolean))
     }
 
+    pub fn from_char(c: char) -> AstNode {
+        Self::from_non_list(Char(c))
+    }
+
+    pub fn from_vector(elements: Vec<AstNode>) -> AstNode {
+        Self::from_non_list(Vector(elements))
+    }
+
+    pub fn from_bytevector(bytes: Vec<u8>) -> AstNode {
+        Self::from_non_list(Bytevector(bytes))
+    }
+
     pub fn to_datum(&self) -> SchemeType {
         match &self.0 {
             NonList(Number(x)) => SchemeType::Number(*x),
             NonList(Symbol(sym)) => new_symbol(sym.get_name()).into(),
-            NonList(SchemeString(stri)) => SchemeType::String(stri.clone().parse().unwrap()),
+            NonList(SchemeString(stri)) => SchemeType::String(stri.as_str().into()),
             List(list) => {
                 let mut builder = ListFactory::new(false);
 
@@ -275,6 +313,15 @@ impl AstNode {
                 builder.build_with_tail(list.list_type.to_datum())
             }
             NonList(Bool(is_true)) => (*is_true).into(),
+            NonList(Char(c)) => SchemeType::Char(*c),
+            NonList(Vector(elements)) => SchemeObject::new(
+                environment::vector_type_id(),
+                elements.iter().map(AstNode::to_datum).collect(),
+            )
+            .into(),
+            NonList(Bytevector(bytes)) => {
+                SchemeType::Bytevector(SchemeBytevector::new(bytes.clone(), false))
+            }
         }
     }
 
@@ -292,6 +339,14 @@ impl AstNode {
             .map(AstList::as_nodes)
     }
 
+    pub fn as_number(&self) -> Option<i64> {
+        if let NonList(Number(num)) = &self.0 {
+            Some(*num)
+        } else {
+            None
+        }
+    }
+
     pub fn as_symbol(&self) -> Option<&AstSymbol> {
         if let NonList(Symbol(sym)) = &self.0 {
             Some(sym)
@@ -308,6 +363,14 @@ impl AstNode {
         }
     }
 
+    pub fn into_string(self) -> Result<String, AstNode> {
+        if let NonList(SchemeString(string)) = self.0 {
+            Ok(string)
+        } else {
+            Err(self)
+        }
+    }
+
     pub fn into_list(self) -> Result<AstList, AstNode> {
         if let List(list) = self.0 {
             Ok(list)
@@ -347,6 +410,9 @@ impl AstNode {
                 }
             }
             NonList(Bool(_)) => "boolean",
+            NonList(Char(_)) => "character",
+            NonList(Vector(_)) => "vector",
+            NonList(Bytevector(_)) => "bytevector",
         }
     }
 }
@@ -0,0 +1,78 @@
+use crate::environment;
+use crate::types::*;
+
+use super::{AstListBuilder, AstNode, AstSymbol};
+
+fn datum_to_string(datum: SchemeType) -> String {
+    datum.into_string().unwrap().to_string()
+}
+
+#[test]
+fn atom_to_datum() {
+    assert_eq!(AstNode::from_number(-5).to_datum(), SchemeType::Number(-5));
+    assert_eq!(AstNode::from_bool(true).to_datum(), environment::s_true());
+    assert_eq!(AstNode::from_bool(false).to_datum(), environment::s_false());
+    assert_eq!(AstNode::from_char('λ').to_datum(), SchemeType::Char('λ'));
+    assert_eq!(
+        AstNode::from(AstSymbol::new("sym")).to_datum(),
+        new_symbol("sym".to_string()).into()
+    );
+}
+
+#[test]
+fn string_to_datum() {
+    for string in &[
+        "",
+        "plain",
+        "\"quoted\"",
+        "back\\slash",
+        "new\nline",
+        "λ ü 😀",
+        "\0",
+    ] {
+        let datum = AstNode::from_string(string.to_string()).to_datum();
+        assert_eq!(datum_to_string(datum), *string);
+    }
+}
+
+#[test]
+fn list_to_datum() {
+    let list: AstNode = vec![AstNode::from_number(1), AstNode::from_number(2)].into();
+    let datum = list.to_datum();
+    assert_eq!(
+        environment::car(datum.clone()).unwrap(),
+        SchemeType::Number(1)
+    );
+    let rest = environment::cdr(datum).unwrap();
+    assert_eq!(
+        environment::car(rest.clone()).unwrap(),
+        SchemeType::Number(2)
+    );
+    assert_eq!(environment::cdr(rest).unwrap(), environment::empty_list());
+
+    let empty: AstNode = Vec::new().into();
+    assert_eq!(empty.to_datum(), environment::empty_list());
+}
+
+#[test]
+fn improper_list_to_datum() {
+    let mut builder = AstListBuilder::new();
+    builder.push(AstNode::from_number(1));
+    builder.push(AstNode::from_string("two".to_string()));
+    let list: AstNode = builder
+        .build_with_tail(AstNode::from_number(3))
+        .unwrap()
+        .into();
+
+    let datum = list.to_datum();
+    assert_eq!(
+        environment::car(datum.clone()).unwrap(),
+        SchemeType::Number(1)
+    );
+    let rest = environment::cdr(datum).unwrap();
+    assert_eq!(
+        datum_to_string(environment::car(rest.clone()).unwrap()),
+        "two"
+    );
+    assert_eq!(environment::cdr(rest).unwrap(), SchemeType::Number(3));
+}
@@ -44,12 +44,16 @@ macro_rules! bind_scheme {
 
 bind_scheme!(pub s_true @unique);
 bind_scheme!(pub s_false @unique);
+bind_scheme!(pub error_object_type_id @unique);
+bind_scheme!(pub promise_type_id @unique);
 
 bind_scheme!(pub empty_list = "$empty-list");
-//bind_scheme!(pub immutable_pair_type_id = "$immutable-pair-type-id");
-//bind_scheme!(pub mutable_pair_type_id = "$mutable-pair-type-id");
+bind_scheme!(pub immutable_pair_type_id = "$immutable-pair-type-id");
+bind_scheme!(pub mutable_pair_type_id = "$mutable-pair-type-id");
 bind_scheme!(pub symbol_type_id = "$symbol-type-id");
+bind_scheme!(pub vector_type_id = "$vector-type-id");
 
+#[cfg(test)]
 bind_scheme!(pub fn car(list) = "car");
+#[cfg(test)]
 bind_scheme!(pub fn cdr(list) = "cdr");
-bind_scheme!(pub fn make_list_factory(is_mutable) = "$make-list-factory");
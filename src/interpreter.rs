@@ -18,20 +18,24 @@
 */
 
 use std::cell::RefCell;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::rc::Rc;
 
 use builtin::BuiltinFunction;
-use runtime_environment::{BaseEnvironment, SCHEME_ENVIRONMENT};
+use runtime_environment::{BaseEnvironment, MINIMAL_ENVIRONMENT, SCHEME_ENVIRONMENT};
 use vm::{run_vm, SchemeFunction, StackFrame};
 
 use crate::ast::AstNode;
 use crate::parser::ParserError;
 use crate::types::*;
 
-pub use self::compiler::CompilerError;
+pub use self::compiler::{with_include_dir, CompilerError};
 
 mod builtin;
 mod compiler;
+mod exception;
+mod profiler;
 pub mod runtime_environment;
 mod vm;
 
@@ -53,15 +57,67 @@ pub fn eval(string: &str) -> Result<SchemeType, RuntimeError> {
     SCHEME_ENVIRONMENT.with(|env| env.eval_str(string))
 }
 
+//Leaves out the procedures std-lib.scm defines, such as list and map, so they can be defined
+//by the program instead.
+pub fn eval_without_prelude(string: &str) -> Result<SchemeType, RuntimeError> {
+    MINIMAL_ENVIRONMENT.with(|env| env.eval_str(string))
+}
+
 #[derive(Debug)]
 pub enum RuntimeError {
     AssertFailed,
+    Raise(SchemeType),
     OutOfBounds,
     DivByZero,
     TypeError,
     EvalError(CompilerError),
     ReadError(ParserError),
     ArgError,
+    //Holds the type name of the value that was called.
+    NotAProcedure(&'static str),
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::AssertFailed => write!(f, "Assertion failed."),
+            RuntimeError::Raise(obj) => match describe_condition(obj) {
+                Some(description) => write!(f, "{}", description),
+                None => write!(f, "Uncaught raise of {:?}.", obj),
+            },
+            RuntimeError::OutOfBounds => write!(f, "Argument out of bounds."),
+            RuntimeError::DivByZero => write!(f, "Division by zero."),
+            RuntimeError::TypeError => write!(f, "Argument has the wrong type."),
+            RuntimeError::EvalError(err) => write!(f, "{}", err),
+            RuntimeError::ReadError(err) => write!(f, "{}", err),
+            RuntimeError::ArgError => write!(f, "Wrong number of arguments."),
+            RuntimeError::NotAProcedure(type_name) => {
+                write!(f, "Cannot call a {}, which is not a procedure.", type_name)
+            }
+        }
+    }
+}
+
+//Error objects are described by their who, message and irritants, using write from
+//std-lib.scm. None if describing the condition fails.
+fn describe_condition(condition: &SchemeType) -> Option<String> {
+    let describe = SCHEME_ENVIRONMENT
+        .with(|env| env.eval_str("$condition->string"))
+        .ok()?;
+    let description = describe
+        .to_function()
+        .ok()?
+        .call(vec![condition.clone()])
+        .ok()?;
+
+    Some(description.into_string().ok()?.to_string())
+}
+
+//Like to_function, but the error names what was called instead.
+fn to_procedure(object: &SchemeType) -> Result<FunctionRef, RuntimeError> {
+    object
+        .to_function()
+        .map_err(|_| RuntimeError::NotAProcedure(object.type_name()))
 }
 
 impl From<CompilerError> for RuntimeError {
@@ -82,10 +138,18 @@ impl From<CastError> for RuntimeError {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Hash)]
 pub struct FunctionRef(FunctionRefInner);
 
 impl FunctionRef {
+    pub fn get_name(&self) -> Option<&str> {
+        self.0.get_name()
+    }
+
+    pub fn get_arity(&self) -> (usize, Option<usize>) {
+        self.0.get_arity()
+    }
+
     pub fn call(self, args: Vec<SchemeType>) -> Result<SchemeType, RuntimeError> {
         let mut stack = Vec::new();
 
@@ -99,18 +163,45 @@ impl FunctionRef {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Hash)]
 enum FunctionRefInner {
     Derived(DerivedFunctionRef),
     Builtin(BuiltinFunction),
 }
 
 impl FunctionRefInner {
+    fn get_name(&self) -> Option<&str> {
+        match self {
+            FunctionRefInner::Builtin(func) => Some(func.get_name()),
+            FunctionRefInner::Derived(func) => func.function.get_name(),
+        }
+    }
+
+    fn get_arity(&self) -> (usize, Option<usize>) {
+        match self {
+            FunctionRefInner::Builtin(func) => func.get_arity(),
+            FunctionRefInner::Derived(func) => {
+                let argc = func.function.get_args() as usize;
+                if func.function.is_vargs() {
+                    (argc, None)
+                } else {
+                    (argc, Some(argc))
+                }
+            }
+        }
+    }
+
     fn call_with_stack(
         self,
         stack: &mut Vec<StackFrame>,
         args: Vec<SchemeType>,
     ) -> Result<Option<SchemeType>, RuntimeError> {
+        if profiler::is_enabled() {
+            if let Some(name) = self.get_name() {
+                profiler::record_call(name)
+            }
+        }
+
         match self {
             FunctionRefInner::Builtin(func) => func.call_with_stack(stack, args),
             FunctionRefInner::Derived(func) => func.call_with_stack(stack, args),
@@ -142,6 +233,16 @@ impl PartialEq for DerivedFunctionRef {
     }
 }
 
+impl Hash for DerivedFunctionRef {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.function).hash(state);
+
+        for capture in self.captures.iter() {
+            Rc::as_ptr(capture).hash(state)
+        }
+    }
+}
+
 impl DerivedFunctionRef {
     fn call_with_stack(
         self,
@@ -18,14 +18,21 @@
 */
 
 use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::time::Instant;
 
+use lazy_static::lazy_static;
+
+use crate::ast::CoreSymbol;
 use crate::environment;
 use crate::interpreter::vm::StackFrame;
 use crate::types::*;
 
-use super::RuntimeError;
+use super::{exception, profiler, to_procedure, RuntimeError};
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Hash)]
 pub enum BuiltinFunction {
     Add,
     Mul,
@@ -34,14 +41,36 @@ pub enum BuiltinFunction {
     Eqv,
     Quotient,
     Remainder,
+    FloorQuotient,
+    FloorRemainder,
+    ModExp,
     GenUnspecified,
     Error,
+    Raise,
+    RaiseContinuable,
+    WithExceptionHandler,
+    Guard,
+    UnwindProtect,
+    DynamicWind,
+    MakePromise,
+    Cons,
     IsObject,
     IsNumber,
     IsChar,
     IsString,
+    IsProcedure,
+    IsBytevector,
+    IsExactNonnegativeInteger,
+    NewBytevector,
+    MakeBytevector,
+    BytevectorLen,
+    GetByte,
+    SetByte,
+    StringToUtf8,
+    Utf8ToString,
     GetTypeId,
     GetField,
+    FieldCount,
     SetField,
     GetChar,
     SetChar,
@@ -49,12 +78,67 @@ pub enum BuiltinFunction {
     NewString,
     StringLen,
     WriteChar,
+    Apply,
+    ProcedureName,
+    ProcedureArity,
+    EqvHash,
+    EqualHash,
+    SymbolAppend,
+    SymbolInterned,
+    Gensym,
+    CharUpcase,
+    CharDowncase,
+    CharFoldcase,
+    DigitValue,
+    CharToInteger,
+    IntegerToChar,
+    StringFoldcase,
+    ProfileStart,
+    ProfileStop,
+    ProfileReport,
+    CurrentJiffy,
+    JiffiesPerSecond,
+}
+
+//Jiffies are microseconds since the first time anything asked for one.
+const JIFFIES_PER_SECOND: i64 = 1_000_000;
+
+lazy_static! {
+    static ref JIFFY_EPOCH: Instant = Instant::now();
 }
 
 fn gen_unspecified() -> SchemeType {
     environment::s_false()
 }
 
+fn to_symbol(object: SchemeType) -> Result<SchemeObject, RuntimeError> {
+    let symbol = object.into_object()?;
+    if symbol.get_type_id() != environment::symbol_type_id() {
+        return Err(RuntimeError::TypeError);
+    }
+    Ok(symbol)
+}
+
+//Numbers outside of 0 to 255 are out of bounds rather than the wrong type.
+fn to_byte(object: SchemeType) -> Result<u8, RuntimeError> {
+    object.to_number()?;
+    object.to_u8().map_err(|_| RuntimeError::OutOfBounds)
+}
+
+//Reads the optional start and end arguments of a sequence of length len.
+fn optional_range(args: &[SchemeType], len: usize) -> Result<(usize, usize), RuntimeError> {
+    let start = args.first().map_or(Ok(0), SchemeType::to_index)?;
+    let end = args.get(1).map_or(Ok(len), SchemeType::to_index)?;
+
+    if args.len() > 2 {
+        Err(RuntimeError::ArgError)
+    } else if start > end || end > len {
+        Err(RuntimeError::OutOfBounds)
+    } else {
+        Ok((start, end))
+    }
+}
+
 fn assert_args<T>(args: &[T], argc: usize, is_vargs: bool) -> Result<(), RuntimeError> {
     if (is_vargs && args.len() < argc) || (!is_vargs && args.len() != argc) {
         Err(RuntimeError::ArgError)
@@ -63,10 +147,214 @@ fn assert_args<T>(args: &[T], argc: usize, is_vargs: bool) -> Result<(), Runtime
     }
 }
 
+//Simple case mappings take every character to exactly one character, so characters whose
+//mapping is more than one character are left alone.
+fn simple_case(c: char, mut mapped: impl Iterator<Item = char>) -> char {
+    match (mapped.next(), mapped.next()) {
+        (Some(single), None) => single,
+        _ => c,
+    }
+}
+
+//Folding uses the lowercase mappings since std does not expose the case folding tables.
+//The two only differ for a handful of characters such as ß.
+fn simple_foldcase(c: char) -> char {
+    simple_case(c, c.to_lowercase())
+}
+
+//Full folding may change the length of the string.
+fn full_foldcase(string: &str) -> String {
+    string.chars().flat_map(char::to_lowercase).collect()
+}
+
+//The zero of each run of ten decimal digits (general category Nd) as of Unicode 14.
+//Every decimal digit in Unicode is part of such a run, in order from zero to nine.
+const DIGIT_ZEROS: [u32; 66] = [
+    0x30, 0x660, 0x6F0, 0x7C0, 0x966, 0x9E6, 0xA66, 0xAE6, 0xB66, 0xBE6, 0xC66, 0xCE6, 0xD66,
+    0xDE6, 0xE50, 0xED0, 0xF20, 0x1040, 0x1090, 0x17E0, 0x1810, 0x1946, 0x19D0, 0x1A80, 0x1A90,
+    0x1B50, 0x1BB0, 0x1C40, 0x1C50, 0xA620, 0xA8D0, 0xA900, 0xA9D0, 0xA9F0, 0xAA50, 0xABF0, 0xFF10,
+    0x104A0, 0x10D30, 0x11066, 0x110F0, 0x11136, 0x111D0, 0x112F0, 0x11450, 0x114D0, 0x11650,
+    0x116C0, 0x11730, 0x118E0, 0x11950, 0x11C50, 0x11D50, 0x11DA0, 0x16A60, 0x16AC0, 0x16B50,
+    0x1D7CE, 0x1D7D8, 0x1D7E2, 0x1D7EC, 0x1D7F6, 0x1E140, 0x1E2F0, 0x1E950, 0x1FBF0,
+];
+
+fn digit_value(c: char) -> Option<u32> {
+    let code = u32::from(c);
+    let run = DIGIT_ZEROS
+        .partition_point(|&zero| zero <= code)
+        .checked_sub(1)?;
+    let value = code - DIGIT_ZEROS[run];
+
+    if value < 10 {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+//Square and multiply with 128 bit intermediates so products of two residues never overflow.
+fn modexp(base: i64, mut exponent: i64, modulus: i64) -> i64 {
+    let modulus = i128::from(modulus);
+    let mut base = i128::from(base).rem_euclid(modulus);
+    let mut result = 1 % modulus;
+
+    while exponent != 0 {
+        if exponent % 2 == 1 {
+            result = result * base % modulus;
+        }
+        base = base * base % modulus;
+        exponent /= 2;
+    }
+
+    result as i64
+}
+
 impl BuiltinFunction {
+    pub fn get_name(self) -> &'static str {
+        match self {
+            BuiltinFunction::Add => "+",
+            BuiltinFunction::Mul => "*",
+            BuiltinFunction::Sub => "-",
+            BuiltinFunction::Compare { invert, mode } => match (invert, mode) {
+                (false, Ordering::Equal) => "=",
+                (false, Ordering::Less) => "<",
+                (true, Ordering::Greater) => "<=",
+                (false, Ordering::Greater) => ">",
+                (true, Ordering::Less) => ">=",
+                (true, Ordering::Equal) => "$not=",
+            },
+            BuiltinFunction::Eqv => "eqv?",
+            BuiltinFunction::Quotient => "quotient",
+            BuiltinFunction::Remainder => "remainder",
+            BuiltinFunction::FloorQuotient => "floor-quotient",
+            BuiltinFunction::FloorRemainder => "floor-remainder",
+            BuiltinFunction::ModExp => "modexp",
+            BuiltinFunction::GenUnspecified => CoreSymbol::GenUnspecified.get_name(),
+            BuiltinFunction::Error => "error",
+            BuiltinFunction::Raise => CoreSymbol::Raise.get_name(),
+            BuiltinFunction::RaiseContinuable => "raise-continuable",
+            BuiltinFunction::WithExceptionHandler => "with-exception-handler",
+            BuiltinFunction::Guard => CoreSymbol::Guard.get_name(),
+            BuiltinFunction::UnwindProtect => CoreSymbol::UnwindProtect.get_name(),
+            BuiltinFunction::DynamicWind => "dynamic-wind",
+            BuiltinFunction::MakePromise => CoreSymbol::MakePromise.get_name(),
+            BuiltinFunction::Cons => CoreSymbol::Cons.get_name(),
+            BuiltinFunction::IsObject => "$object?",
+            BuiltinFunction::IsNumber => "number?",
+            BuiltinFunction::IsChar => "char?",
+            BuiltinFunction::IsString => "string?",
+            BuiltinFunction::IsProcedure => "procedure?",
+            BuiltinFunction::IsBytevector => "bytevector?",
+            BuiltinFunction::IsExactNonnegativeInteger => "exact-nonnegative-integer?",
+            BuiltinFunction::NewBytevector => "bytevector",
+            BuiltinFunction::MakeBytevector => "make-bytevector",
+            BuiltinFunction::BytevectorLen => "bytevector-length",
+            BuiltinFunction::GetByte => "bytevector-u8-ref",
+            BuiltinFunction::SetByte => "bytevector-u8-set!",
+            BuiltinFunction::StringToUtf8 => "string->utf8",
+            BuiltinFunction::Utf8ToString => "utf8->string",
+            BuiltinFunction::GetTypeId => "$object-type-id-get",
+            BuiltinFunction::GetField => "$object-field-get",
+            BuiltinFunction::FieldCount => "$object-field-count",
+            BuiltinFunction::SetField => "$object-field-set!",
+            BuiltinFunction::GetChar => "string-ref",
+            BuiltinFunction::SetChar => "string-set!",
+            BuiltinFunction::NewObject => "$make-object",
+            BuiltinFunction::NewString => "make-string",
+            BuiltinFunction::StringLen => "string-length",
+            BuiltinFunction::WriteChar => "write-char",
+            BuiltinFunction::Apply => "apply",
+            BuiltinFunction::ProcedureName => "$procedure-name",
+            BuiltinFunction::ProcedureArity => "procedure-arity",
+            BuiltinFunction::EqvHash => "eqv-hash",
+            BuiltinFunction::EqualHash => "equal-hash",
+            BuiltinFunction::SymbolAppend => "symbol-append",
+            BuiltinFunction::SymbolInterned => "symbol-interned?",
+            BuiltinFunction::Gensym => "gensym",
+            BuiltinFunction::CharUpcase => "char-upcase",
+            BuiltinFunction::CharDowncase => "char-downcase",
+            BuiltinFunction::CharFoldcase => "char-foldcase",
+            BuiltinFunction::DigitValue => "digit-value",
+            BuiltinFunction::CharToInteger => "char->integer",
+            BuiltinFunction::IntegerToChar => "integer->char",
+            BuiltinFunction::StringFoldcase => "string-foldcase",
+            BuiltinFunction::ProfileStart => "profile-start!",
+            BuiltinFunction::ProfileStop => "profile-stop!",
+            BuiltinFunction::ProfileReport => "profile-report",
+            BuiltinFunction::CurrentJiffy => "current-jiffy",
+            BuiltinFunction::JiffiesPerSecond => "jiffies-per-second",
+        }
+    }
+
+    //The minimum argument count and the maximum, if there is one. There is no catch-all arm so a
+    //new builtin has to be given its arity here.
+    pub fn get_arity(self) -> (usize, Option<usize>) {
+        match self {
+            BuiltinFunction::Add
+            | BuiltinFunction::Mul
+            | BuiltinFunction::GenUnspecified
+            | BuiltinFunction::NewBytevector
+            | BuiltinFunction::SymbolAppend => (0, None),
+            BuiltinFunction::Sub | BuiltinFunction::Error | BuiltinFunction::NewObject => (1, None),
+            BuiltinFunction::Compare { .. } | BuiltinFunction::Apply => (2, None),
+            BuiltinFunction::MakeBytevector | BuiltinFunction::NewString => (1, Some(2)),
+            BuiltinFunction::StringToUtf8 | BuiltinFunction::Utf8ToString => (1, Some(3)),
+            BuiltinFunction::ProfileStart
+            | BuiltinFunction::ProfileStop
+            | BuiltinFunction::ProfileReport
+            | BuiltinFunction::CurrentJiffy
+            | BuiltinFunction::JiffiesPerSecond
+            | BuiltinFunction::Gensym => (0, Some(0)),
+            BuiltinFunction::Eqv
+            | BuiltinFunction::Quotient
+            | BuiltinFunction::Remainder
+            | BuiltinFunction::FloorQuotient
+            | BuiltinFunction::FloorRemainder
+            | BuiltinFunction::WithExceptionHandler
+            | BuiltinFunction::Guard
+            | BuiltinFunction::UnwindProtect
+            | BuiltinFunction::MakePromise
+            | BuiltinFunction::Cons
+            | BuiltinFunction::GetByte
+            | BuiltinFunction::GetField
+            | BuiltinFunction::GetChar => (2, Some(2)),
+            BuiltinFunction::ModExp
+            | BuiltinFunction::DynamicWind
+            | BuiltinFunction::SetByte
+            | BuiltinFunction::SetField
+            | BuiltinFunction::SetChar => (3, Some(3)),
+            BuiltinFunction::Raise
+            | BuiltinFunction::RaiseContinuable
+            | BuiltinFunction::IsObject
+            | BuiltinFunction::IsNumber
+            | BuiltinFunction::IsChar
+            | BuiltinFunction::IsString
+            | BuiltinFunction::IsProcedure
+            | BuiltinFunction::IsBytevector
+            | BuiltinFunction::IsExactNonnegativeInteger
+            | BuiltinFunction::BytevectorLen
+            | BuiltinFunction::GetTypeId
+            | BuiltinFunction::FieldCount
+            | BuiltinFunction::StringLen
+            | BuiltinFunction::WriteChar
+            | BuiltinFunction::ProcedureName
+            | BuiltinFunction::ProcedureArity
+            | BuiltinFunction::EqvHash
+            | BuiltinFunction::EqualHash
+            | BuiltinFunction::SymbolInterned
+            | BuiltinFunction::CharUpcase
+            | BuiltinFunction::CharDowncase
+            | BuiltinFunction::CharFoldcase
+            | BuiltinFunction::DigitValue
+            | BuiltinFunction::CharToInteger
+            | BuiltinFunction::IntegerToChar
+            | BuiltinFunction::StringFoldcase => (1, Some(1)),
+        }
+    }
+
     pub fn call_with_stack(
         self,
-        _stack: &mut Vec<StackFrame>,
+        stack: &mut Vec<StackFrame>,
         mut args: Vec<SchemeType>,
     ) -> Result<Option<SchemeType>, RuntimeError> {
         match self {
@@ -118,7 +406,10 @@ impl BuiltinFunction {
 
                 Ok(Some((args[0] == args[1]).into()))
             }
-            BuiltinFunction::Quotient | BuiltinFunction::Remainder => {
+            BuiltinFunction::Quotient
+            | BuiltinFunction::Remainder
+            | BuiltinFunction::FloorQuotient
+            | BuiltinFunction::FloorRemainder => {
                 assert_args(&args, 2, false)?;
 
                 let b = args.pop().unwrap().to_number()?;
@@ -128,17 +419,86 @@ impl BuiltinFunction {
                     return Err(RuntimeError::DivByZero);
                 }
 
+                //Truncating division rounds toward zero. Floor division rounds down instead, so
+                //a nonzero remainder takes the sign of the divisor.
+                let rounds_down = a % b != 0 && (a < 0) != (b < 0);
                 let res = match self {
                     BuiltinFunction::Quotient => a / b,
                     BuiltinFunction::Remainder => a % b,
+                    BuiltinFunction::FloorQuotient if rounds_down => a / b - 1,
+                    BuiltinFunction::FloorQuotient => a / b,
+                    BuiltinFunction::FloorRemainder if rounds_down => a % b + b,
+                    BuiltinFunction::FloorRemainder => a % b,
                     _ => unreachable!(),
                 };
 
                 Ok(Some(SchemeType::Number(res)))
             }
+            BuiltinFunction::ModExp => {
+                assert_args(&args, 3, false)?;
+
+                let modulus = args.pop().unwrap().to_number()?;
+                let exponent = args.pop().unwrap().to_number()?;
+                let base = args.pop().unwrap().to_number()?;
+
+                if modulus == 0 {
+                    return Err(RuntimeError::DivByZero);
+                }
+                //Negative exponents need modular inverses.
+                if modulus < 0 || exponent < 0 {
+                    return Err(RuntimeError::ArgError);
+                }
+
+                Ok(Some(SchemeType::Number(modexp(base, exponent, modulus))))
+            }
 
             BuiltinFunction::GenUnspecified => Ok(Some(gen_unspecified())),
-            BuiltinFunction::Error => Err(RuntimeError::AssertFailed),
+            BuiltinFunction::Error => {
+                assert_args(&args, 1, true)?;
+
+                //Accepts both (error message irritant ...) and (error who message irritant ...).
+                let who = if let SchemeType::String(_) = args[0] {
+                    environment::s_false()
+                } else {
+                    args.remove(0)
+                };
+                assert_args(&args, 1, true)?;
+                let message = args.remove(0);
+
+                exception::raise(exception::new_error_object(who, message, args)).map(Some)
+            }
+            BuiltinFunction::Raise => {
+                assert_args(&args, 1, false)?;
+
+                exception::raise(args.pop().unwrap()).map(Some)
+            }
+            BuiltinFunction::RaiseContinuable => {
+                assert_args(&args, 1, false)?;
+
+                exception::raise_continuable(args.pop().unwrap()).map(Some)
+            }
+            BuiltinFunction::WithExceptionHandler => {
+                assert_args(&args, 2, false)?;
+
+                let thunk = args.pop().unwrap().to_function()?;
+                let handler = args.pop().unwrap().to_function()?;
+
+                exception::with_exception_handler(handler, thunk).map(Some)
+            }
+            BuiltinFunction::Guard => {
+                assert_args(&args, 2, false)?;
+
+                let handler = args.pop().unwrap().to_function()?;
+                let thunk = args.pop().unwrap().to_function()?;
+
+                match exception::guard(thunk) {
+                    //The handler runs after the guard's handlers are uninstalled.
+                    Err(RuntimeError::Raise(condition)) => {
+                        handler.0.call_with_stack(stack, vec![condition])
+                    }
+                    result => result.map(Some),
+                }
+            }
             BuiltinFunction::IsObject => {
                 assert_args(&args, 1, false)?;
 
@@ -191,6 +551,109 @@ impl BuiltinFunction {
                     .into(),
                 ))
             }
+            BuiltinFunction::IsProcedure => {
+                assert_args(&args, 1, false)?;
+
+                let object = args.pop().unwrap();
+                Ok(Some(matches!(object, SchemeType::Function(_)).into()))
+            }
+            BuiltinFunction::IsBytevector => {
+                assert_args(&args, 1, false)?;
+
+                let object = args.pop().unwrap();
+                Ok(Some(matches!(object, SchemeType::Bytevector(_)).into()))
+            }
+            //True exactly when the object can be used as an index.
+            BuiltinFunction::IsExactNonnegativeInteger => {
+                assert_args(&args, 1, false)?;
+
+                let object = args.pop().unwrap();
+                Ok(Some(object.to_index().is_ok().into()))
+            }
+            BuiltinFunction::NewBytevector => {
+                let bytes = args.into_iter().map(to_byte).collect::<Result<_, _>>()?;
+
+                Ok(Some(SchemeBytevector::new(bytes, true).into()))
+            }
+            BuiltinFunction::MakeBytevector => {
+                let fill = match args.len() {
+                    1 => 0,
+                    2 => to_byte(args.pop().unwrap())?,
+                    _ => return Err(RuntimeError::ArgError),
+                };
+                let size = args.pop().unwrap().to_index()?;
+
+                Ok(Some(SchemeBytevector::new(vec![fill; size], true).into()))
+            }
+            BuiltinFunction::BytevectorLen => {
+                assert_args(&args, 1, false)?;
+
+                let bytes = args.pop().unwrap().into_bytevector()?;
+
+                Ok(Some(bytes.len().into()))
+            }
+            BuiltinFunction::GetByte => {
+                assert_args(&args, 2, false)?;
+
+                let index = args.pop().unwrap().to_index()?;
+                let bytes = args.pop().unwrap().into_bytevector()?;
+
+                bytes
+                    .get(index)
+                    .ok_or(RuntimeError::OutOfBounds)
+                    .map(|byte| Some(SchemeType::Number(byte.into())))
+            }
+            BuiltinFunction::SetByte => {
+                assert_args(&args, 3, false)?;
+
+                let byte = to_byte(args.pop().unwrap())?;
+                let index = args.pop().unwrap().to_index()?;
+                let bytes = args.pop().unwrap().into_bytevector()?;
+
+                if !bytes.is_mutable() {
+                    return Err(RuntimeError::AssertFailed);
+                }
+
+                bytes
+                    .set(index, byte)
+                    .map(|_| Some(gen_unspecified()))
+                    .map_err(|_| RuntimeError::OutOfBounds)
+            }
+            BuiltinFunction::StringToUtf8 => {
+                assert_args(&args, 1, true)?;
+
+                let string = args.remove(0).into_string()?;
+                let (start, end) = optional_range(&args, string.len())?;
+
+                let utf8: String = (start..end)
+                    .map(|index| string.get(index).unwrap())
+                    .collect();
+
+                Ok(Some(SchemeBytevector::new(utf8.into_bytes(), true).into()))
+            }
+            BuiltinFunction::Utf8ToString => {
+                assert_args(&args, 1, true)?;
+
+                let bytes = args.remove(0).into_bytevector()?;
+                let (start, end) = optional_range(&args, bytes.len())?;
+
+                let utf8 = (start..end)
+                    .map(|index| bytes.get(index).unwrap())
+                    .collect();
+
+                match String::from_utf8(utf8) {
+                    Ok(string) => Ok(Some(SchemeType::String(string.as_str().into()))),
+                    Err(_) => {
+                        let message: SchemeString = "Invalid UTF-8.".into();
+                        let error = exception::new_error_object(
+                            new_symbol(self.get_name().to_string()).into(),
+                            message.into(),
+                            vec![bytes.into()],
+                        );
+                        exception::raise(error).map(Some)
+                    }
+                }
+            }
             BuiltinFunction::GetTypeId => {
                 assert_args(&args, 1, false)?;
 
@@ -208,6 +671,13 @@ impl BuiltinFunction {
                     .ok_or(RuntimeError::OutOfBounds)
                     .map(Some)
             }
+            BuiltinFunction::FieldCount => {
+                assert_args(&args, 1, false)?;
+
+                let object = args.pop().unwrap().into_object()?;
+
+                Ok(Some(object.field_count().into()))
+            }
             BuiltinFunction::SetField => {
                 assert_args(&args, 3, false)?;
 
@@ -252,8 +722,9 @@ impl BuiltinFunction {
             BuiltinFunction::NewString => {
                 let fill;
 
+                //Without a fill the string is all spaces.
                 if args.len() == 1 {
-                    fill = '\0';
+                    fill = ' ';
                 } else if args.len() == 2 {
                     fill = args.pop().unwrap().to_char()?;
                 } else {
@@ -279,6 +750,217 @@ impl BuiltinFunction {
                 print!("{}", c);
                 Ok(Some(gen_unspecified()))
             }
+            BuiltinFunction::Apply => {
+                assert_args(&args, 2, true)?;
+
+                let function = to_procedure(&args.remove(0))?;
+                let spread_args = args.pop().unwrap().into_vec()?;
+                args.extend(spread_args);
+
+                function.0.call_with_stack(stack, args)
+            }
+            BuiltinFunction::ProcedureName => {
+                assert_args(&args, 1, false)?;
+
+                let function = args.pop().unwrap().to_function()?;
+
+                Ok(Some(match function.get_name() {
+                    Some(name) => SchemeType::String(name.into()),
+                    None => environment::s_false(),
+                }))
+            }
+            BuiltinFunction::ProcedureArity => {
+                assert_args(&args, 1, false)?;
+
+                let (min, max) = args.pop().unwrap().to_function()?.get_arity();
+                let max = match max {
+                    Some(max) => SchemeType::Number(max as i64),
+                    None => environment::s_false(),
+                };
+
+                let mut arity = ListFactory::new(true);
+                arity.push(SchemeType::Number(min as i64));
+                Ok(Some(arity.build_with_tail(max)))
+            }
+            BuiltinFunction::EqvHash | BuiltinFunction::EqualHash => {
+                assert_args(&args, 1, false)?;
+
+                let object = args.pop().unwrap();
+                let mut hasher = DefaultHasher::new();
+
+                if let BuiltinFunction::EqvHash = self {
+                    object.hash(&mut hasher)
+                } else {
+                    object.equal_hash(&mut hasher)
+                }
+
+                //Drop the top bit so the hash is never negative.
+                Ok(Some(SchemeType::Number((hasher.finish() >> 1) as i64)))
+            }
+            BuiltinFunction::SymbolAppend => {
+                let mut name = String::new();
+
+                for arg in args {
+                    let symbol = to_symbol(arg)?;
+                    name.push_str(&symbol.get_field(0).unwrap().into_string()?.to_string());
+                }
+
+                Ok(Some(new_symbol(name).into()))
+            }
+            BuiltinFunction::SymbolInterned => {
+                assert_args(&args, 1, false)?;
+
+                let symbol = to_symbol(args.pop().unwrap())?;
+
+                Ok(Some(is_interned(&symbol).into()))
+            }
+            BuiltinFunction::Gensym => {
+                static GENSYM_COUNT: AtomicU64 = AtomicU64::new(0);
+
+                assert_args(&args, 0, false)?;
+
+                let count = GENSYM_COUNT.fetch_add(1, AtomicOrdering::Relaxed);
+
+                Ok(Some(new_uninterned_symbol(format!("g{}", count)).into()))
+            }
+            BuiltinFunction::CharUpcase => {
+                assert_args(&args, 1, false)?;
+
+                let c = args.pop().unwrap().to_char()?;
+
+                Ok(Some(SchemeType::Char(simple_case(c, c.to_uppercase()))))
+            }
+            BuiltinFunction::CharDowncase => {
+                assert_args(&args, 1, false)?;
+
+                let c = args.pop().unwrap().to_char()?;
+
+                Ok(Some(SchemeType::Char(simple_case(c, c.to_lowercase()))))
+            }
+            BuiltinFunction::CharFoldcase => {
+                assert_args(&args, 1, false)?;
+
+                let c = args.pop().unwrap().to_char()?;
+
+                Ok(Some(SchemeType::Char(simple_foldcase(c))))
+            }
+            BuiltinFunction::DigitValue => {
+                assert_args(&args, 1, false)?;
+
+                let c = args.pop().unwrap().to_char()?;
+
+                Ok(Some(match digit_value(c) {
+                    Some(value) => SchemeType::Number(i64::from(value)),
+                    None => environment::s_false(),
+                }))
+            }
+            BuiltinFunction::CharToInteger => {
+                assert_args(&args, 1, false)?;
+
+                let c = args.pop().unwrap().to_char()?;
+
+                Ok(Some(SchemeType::Number(i64::from(u32::from(c)))))
+            }
+            BuiltinFunction::IntegerToChar => {
+                assert_args(&args, 1, false)?;
+
+                let object = args.pop().unwrap();
+                object.to_number()?;
+
+                //Surrogates and numbers past 0x10FFFF are not characters.
+                let code: u32 = object.to_ranged().map_err(|_| RuntimeError::OutOfBounds)?;
+                let c = std::char::from_u32(code).ok_or(RuntimeError::OutOfBounds)?;
+
+                Ok(Some(SchemeType::Char(c)))
+            }
+            BuiltinFunction::StringFoldcase => {
+                assert_args(&args, 1, false)?;
+
+                let string = args.pop().unwrap().into_string()?.to_string();
+
+                Ok(Some(SchemeType::String(
+                    full_foldcase(&string).as_str().into(),
+                )))
+            }
+            BuiltinFunction::UnwindProtect => {
+                assert_args(&args, 2, false)?;
+
+                let after = args.pop().unwrap().to_function()?;
+                let thunk = args.pop().unwrap().to_function()?;
+
+                //after runs whether thunk returns or fails.
+                let ret = thunk.call(Vec::new());
+                after.call(Vec::new())?;
+                ret.map(Some)
+            }
+            BuiltinFunction::MakePromise => {
+                assert_args(&args, 2, false)?;
+
+                let value = args.pop().unwrap();
+                let is_done = args.pop().unwrap();
+
+                //The state is a pair so promises chained by delay-force can share it.
+                let state =
+                    SchemeObject::new(environment::mutable_pair_type_id(), vec![is_done, value]);
+
+                Ok(Some(
+                    SchemeObject::new(environment::promise_type_id(), vec![state.into()]).into(),
+                ))
+            }
+            BuiltinFunction::Cons => {
+                assert_args(&args, 2, false)?;
+
+                Ok(Some(
+                    SchemeObject::new(environment::mutable_pair_type_id(), args).into(),
+                ))
+            }
+            BuiltinFunction::DynamicWind => {
+                assert_args(&args, 3, false)?;
+
+                let after = args.pop().unwrap().to_function()?;
+                let thunk = args.pop().unwrap().to_function()?;
+                let before = args.pop().unwrap().to_function()?;
+
+                //thunk is not in tail position since after has to run once it returns.
+                //It gets its own call so tail calls inside it still run in constant space.
+                before.call(Vec::new())?;
+                let ret = thunk.call(Vec::new());
+                after.call(Vec::new())?;
+                ret.map(Some)
+            }
+            BuiltinFunction::ProfileStart => {
+                assert_args(&args, 0, false)?;
+                profiler::start();
+                Ok(Some(gen_unspecified()))
+            }
+            BuiltinFunction::ProfileStop => {
+                assert_args(&args, 0, false)?;
+                profiler::stop();
+                Ok(Some(gen_unspecified()))
+            }
+            BuiltinFunction::ProfileReport => {
+                assert_args(&args, 0, false)?;
+
+                let mut report = ListFactory::new(true);
+                for (name, count) in profiler::report() {
+                    let mut entry = ListFactory::new(true);
+                    entry.push(new_symbol(name).into());
+                    report.push(entry.build_with_tail(SchemeType::Number(count as i64)));
+                }
+                Ok(Some(report.build()))
+            }
+            BuiltinFunction::CurrentJiffy => {
+                assert_args(&args, 0, false)?;
+
+                let micros = JIFFY_EPOCH.elapsed().as_micros();
+
+                Ok(Some(SchemeType::Number(micros as i64)))
+            }
+            BuiltinFunction::JiffiesPerSecond => {
+                assert_args(&args, 0, false)?;
+
+                Ok(Some(SchemeType::Number(JIFFIES_PER_SECOND)))
+            }
         }
     }
 }
@@ -17,11 +17,15 @@
     along with scheme-oxide.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::fs;
 use std::mem::replace;
+use std::path::{Path, PathBuf};
 
 use crate::ast::{AstListBuilder, AstNode, AstSymbol, CoreSymbol};
 use crate::interpreter::vm::{SchemeFunction, Statement, StatementType};
+use crate::parser::Parser;
 
 use self::compiler_type::CompilerType;
 use self::error::AstCastErrorImpl;
@@ -68,7 +72,7 @@ pub fn parse_define(mut define: Vec<AstNode>) -> Result<(AstSymbol, AstNode), Co
             formal_list.build_with_tail(end).unwrap().into()
         };
 
-        let mut lambda_list = vec![CoreSymbol::Lambda.into(), formals];
+        let mut lambda_list = vec![CoreSymbol::NamedLambda.into(), name.clone().into(), formals];
         lambda_list.append(&mut define);
 
         Ok((name, lambda_list.into()))
@@ -106,11 +110,24 @@ impl EnvironmentFrame {
     pub fn add_stage0_macros(&mut self) {
         self.push_builtin_macro(
             AstSymbol::new("lambda"),
-            BuiltinMacro::Lambda { is_stage_1: true },
+            BuiltinMacro::Lambda {
+                is_stage_1: true,
+                is_named: false,
+            },
         );
         self.push_builtin_macro(
             CoreSymbol::Lambda.into(),
-            BuiltinMacro::Lambda { is_stage_1: true },
+            BuiltinMacro::Lambda {
+                is_stage_1: true,
+                is_named: false,
+            },
+        );
+        self.push_builtin_macro(
+            CoreSymbol::NamedLambda.into(),
+            BuiltinMacro::Lambda {
+                is_stage_1: true,
+                is_named: true,
+            },
         );
         self.push_builtin_macro(AstSymbol::new("if"), BuiltinMacro::If);
         self.push_builtin_macro(CoreSymbol::If.into(), BuiltinMacro::If);
@@ -127,19 +144,43 @@ impl EnvironmentFrame {
         self.push_builtin_macro(AstSymbol::new("and"), BuiltinMacro::And);
         self.push_builtin_macro(CoreSymbol::And.into(), BuiltinMacro::And);
         self.push_builtin_macro(AstSymbol::new("cond"), BuiltinMacro::Cond);
+        self.push_builtin_macro(CoreSymbol::Cond.into(), BuiltinMacro::Cond);
+        self.push_builtin_macro(AstSymbol::new("guard"), BuiltinMacro::Guard);
+        self.push_builtin_macro(AstSymbol::new("fluid-let"), BuiltinMacro::FluidLet);
+        self.push_builtin_macro(AstSymbol::new("when"), BuiltinMacro::When);
+        self.push_builtin_macro(AstSymbol::new("unless"), BuiltinMacro::Unless);
+        self.push_builtin_macro(AstSymbol::new("dotimes"), BuiltinMacro::Dotimes);
+        self.push_builtin_macro(AstSymbol::new("delay"), BuiltinMacro::Delay);
+        self.push_builtin_macro(AstSymbol::new("delay-force"), BuiltinMacro::DelayForce);
+        self.push_builtin_macro(AstSymbol::new("cons-stream"), BuiltinMacro::ConsStream);
         self.push_builtin_macro(AstSymbol::new("letrec"), BuiltinMacro::LetRec);
         self.push_builtin_macro(CoreSymbol::LetRec.into(), BuiltinMacro::LetRec);
+        self.push_builtin_macro(AstSymbol::new("letrec*"), BuiltinMacro::LetRecStar);
+        self.push_builtin_macro(CoreSymbol::LetRecStar.into(), BuiltinMacro::LetRecStar);
         self.push_builtin_macro(CoreSymbol::BeginProgram.into(), BuiltinMacro::BeginProgram);
     }
 
     pub fn add_stage2_macros(&mut self) {
         self.push_builtin_macro(
             AstSymbol::new("lambda"),
-            BuiltinMacro::Lambda { is_stage_1: false },
+            BuiltinMacro::Lambda {
+                is_stage_1: false,
+                is_named: false,
+            },
         );
         self.push_builtin_macro(
             CoreSymbol::Lambda.into(),
-            BuiltinMacro::Lambda { is_stage_1: false },
+            BuiltinMacro::Lambda {
+                is_stage_1: false,
+                is_named: false,
+            },
+        );
+        self.push_builtin_macro(
+            CoreSymbol::NamedLambda.into(),
+            BuiltinMacro::Lambda {
+                is_stage_1: false,
+                is_named: true,
+            },
         );
         self.push_builtin_macro(AstSymbol::new("quote"), BuiltinMacro::Quote);
         self.push_builtin_macro(CoreSymbol::Quote.into(), BuiltinMacro::Quote);
@@ -176,6 +217,144 @@ fn gen_tail_body(mut code: Vec<AstNode>) -> Result<Vec<CompilerAction>, Compiler
     Ok(stack)
 }
 
+fn is_form(node: &AstNode, keyword: &str) -> bool {
+    node.as_proper_list()
+        .and_then(|list| list.first())
+        .and_then(AstNode::as_symbol)
+        .map(|name| *name == AstSymbol::new(keyword))
+        .unwrap_or(false)
+}
+
+fn is_define(node: &AstNode) -> bool {
+    is_form(node, "define")
+}
+
+//Explains why an unbound define or include form can't be used where it is. Bodies and programs
+//handle these forms before compiling, so any left over were in expression position.
+fn misplaced_form_error(keyword: &AstSymbol) -> Option<CompilerError> {
+    if *keyword == AstSymbol::new("define") {
+        Some(CompilerError::syntax(
+            "define must come before any expressions in a body or program.",
+        ))
+    } else if *keyword == AstSymbol::new("include") {
+        Some(CompilerError::syntax(
+            "include can only be used directly in a body or program.",
+        ))
+    } else {
+        None
+    }
+}
+
+//Includes nested deeper than this are assumed to be a file including itself.
+const MAX_INCLUDE_DEPTH: usize = 64;
+
+thread_local! {
+    //Relative paths in includes outside of any included file are resolved against this.
+    static INCLUDE_DIR: RefCell<PathBuf> = const { RefCell::new(PathBuf::new()) };
+}
+
+//Runs body with relative includes in the program resolved against dir.
+pub fn with_include_dir<T>(dir: &Path, body: impl FnOnce() -> T) -> T {
+    let old_dir = INCLUDE_DIR.with(|include_dir| include_dir.replace(dir.to_path_buf()));
+    let ret = body();
+    INCLUDE_DIR.with(|include_dir| include_dir.replace(old_dir));
+    ret
+}
+
+//Reads the forms of each file named by an include, paired with the directory of that file.
+fn read_includes(
+    files: Vec<AstNode>,
+    dir: &Path,
+) -> Result<Vec<(AstNode, PathBuf)>, CompilerError> {
+    let mut forms = Vec::new();
+
+    for file in files {
+        let path = dir.join(file.into_string().into_compiler_result("include")?);
+        let text = fs::read_to_string(&path)
+            .map_err(|err| CompilerError::include(&path, &err.to_string()))?;
+        let file_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+
+        for form in Parser::new(&text) {
+            let form = form.map_err(|err| CompilerError::include(&path, &err.to_string()))?;
+            forms.push((form, file_dir.clone()));
+        }
+    }
+
+    Ok(forms)
+}
+
+//Splices the forms of included files into the body in place of each include. Relative paths
+//are resolved against the directory of the file the include is in.
+fn expand_includes(
+    body: Vec<AstNode>,
+    function: &PartialFunction,
+) -> Result<Vec<AstNode>, CompilerError> {
+    if function.is_bounded(&AstSymbol::new("include"))
+        || !body.iter().any(|node| is_form(node, "include"))
+    {
+        return Ok(body);
+    }
+
+    let base_dir = INCLUDE_DIR.with(|include_dir| include_dir.borrow().clone());
+    let mut pending: Vec<_> = body
+        .into_iter()
+        .rev()
+        .map(|node| (node, base_dir.clone(), 0))
+        .collect();
+    let mut expanded = Vec::new();
+
+    while let Some((node, dir, depth)) = pending.pop() {
+        if !is_form(&node, "include") {
+            expanded.push(node);
+            continue;
+        }
+
+        if depth == MAX_INCLUDE_DEPTH {
+            return Err(CompilerError::syntax("Includes are nested too deeply."));
+        }
+
+        let mut files = node.into_proper_list().unwrap();
+        files.remove(0);
+        for (form, file_dir) in read_includes(files, &dir)?.into_iter().rev() {
+            pending.push((form, file_dir, depth + 1));
+        }
+    }
+
+    Ok(expanded)
+}
+
+//Turns the internal defines at the start of a body into a letrec* binding the rest of the body.
+fn expand_internal_defines(
+    body: Vec<AstNode>,
+    function: &PartialFunction,
+) -> Result<Vec<AstNode>, CompilerError> {
+    let body = expand_includes(body, function)?;
+
+    if function.is_bounded(&AstSymbol::new("define")) {
+        return Ok(body);
+    }
+
+    let mut body_iter = body.into_iter().peekable();
+    let mut bindings = Vec::new();
+
+    while let Some(true) = body_iter.peek().map(is_define) {
+        let mut define = body_iter.next().unwrap().into_proper_list().unwrap();
+        define.remove(0);
+
+        let (name, expr) = parse_define(define)?;
+        bindings.push(vec![name.into(), expr].into());
+    }
+
+    if bindings.is_empty() {
+        return Ok(body_iter.collect());
+    }
+
+    let mut letrec_list = vec![CoreSymbol::LetRecStar.into(), bindings.into()];
+    letrec_list.extend(body_iter);
+
+    Ok(vec![letrec_list.into()])
+}
+
 pub struct PartialFunction {
     compiled_code: SchemeFunction,
     environment: EnvironmentFrame,
@@ -234,20 +413,35 @@ struct LetDef {
 }
 
 impl LetDef {
-    fn from_raw_let(raw_defs: Vec<AstNode>) -> Result<Vec<LetDef>, CompilerError> {
+    //Checks that every binding is of the form (name init), what is the name of the binding form
+    //used in error messages.
+    fn from_raw_let(raw_defs: Vec<AstNode>, what: &str) -> Result<Vec<LetDef>, CompilerError> {
         let mut defs = Vec::new();
 
         for definition_or_err in raw_defs {
-            let bad_list_err = "Each let binding must be a proper list of 2.";
-
-            let mut definition = if let Ok(def) = definition_or_err.into_proper_list() {
-                def
-            } else {
-                return Err(CompilerError::syntax(bad_list_err));
-            };
-
-            if definition.len() != 2 {
-                return Err(CompilerError::syntax(bad_list_err));
+            let mut definition = definition_or_err.into_proper_list().map_err(|node| {
+                CompilerError::syntax(&format!(
+                    "Each {} binding must be a list of a name and an init expression, got a {}.",
+                    what,
+                    node.get_name()
+                ))
+            })?;
+
+            match definition.len() {
+                2 => (),
+                0 => return Err(CompilerError::syntax(&format!("Empty {} binding.", what))),
+                1 => {
+                    return Err(CompilerError::syntax(&format!(
+                        "{} binding is missing an init expression.",
+                        what
+                    )))
+                }
+                _ => {
+                    return Err(CompilerError::syntax(&format!(
+                        "{} binding has more than one init expression.",
+                        what
+                    )))
+                }
             }
 
             let binding = definition.pop().unwrap();
@@ -255,7 +449,7 @@ impl LetDef {
                 .pop()
                 .unwrap()
                 .into_symbol()
-                .into_compiler_result("Let")?;
+                .into_compiler_result(what)?;
 
             defs.push(LetDef { formal, binding })
         }
@@ -267,6 +461,7 @@ impl LetDef {
 #[derive(Debug)]
 pub struct LambdaBuilder {
     actions: Vec<CompilerAction>,
+    name: Option<AstSymbol>,
     args: Vec<AstSymbol>,
     vargs: Option<AstSymbol>,
     macros: Vec<(AstSymbol, CompilerType)>,
@@ -277,6 +472,7 @@ impl LambdaBuilder {
     fn new(actions: Vec<CompilerAction>, state: CompilerState) -> Self {
         Self {
             actions,
+            name: None,
             args: Vec::new(),
             vargs: None,
             macros: Vec::new(),
@@ -288,6 +484,19 @@ impl LambdaBuilder {
         Ok(Self::new(gen_tail_body(body)?, state))
     }
 
+    //Like from_body_exprs but for bodies written by the user, which may start with defines.
+    fn from_body(
+        body: Vec<AstNode>,
+        function: &PartialFunction,
+        state: CompilerState,
+    ) -> Result<Self, CompilerError> {
+        Self::from_body_exprs(expand_internal_defines(body, function)?, state)
+    }
+
+    fn set_name(&mut self, name: AstSymbol) {
+        self.name = Some(name)
+    }
+
     fn add_args<T>(&mut self, args: T)
     where
         T: IntoIterator<Item = AstSymbol>,
@@ -328,10 +537,15 @@ impl LambdaBuilder {
             new_env.map.insert(name, s_macro);
         }
 
+        let mut compiled_code = SchemeFunction::new(arg_count, is_vargs);
+        if let Some(name) = self.name {
+            compiled_code.set_name(name.get_name());
+        }
+
         let parent = replace(
             function,
             PartialFunction {
-                compiled_code: SchemeFunction::new(arg_count, is_vargs),
+                compiled_code,
                 environment: new_env,
                 parent: None,
             },
@@ -488,7 +702,9 @@ pub fn compile_function(
 
                         //If the name is a macro, expand the macro
                         if let Some(function_name) = function_object.as_symbol() {
-                            calling_function = function.lookup(function_name)?;
+                            calling_function = function.lookup(function_name).map_err(|err| {
+                                misplaced_form_error(function_name).unwrap_or(err)
+                            })?;
                             expand_as_fn = calling_function.get_expand_as_fn_fn();
                         }
 
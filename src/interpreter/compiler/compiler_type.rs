@@ -169,10 +169,11 @@ impl CompilerType {
                         args.pop().unwrap(),
                     ];
 
+                    //Evaluate the new value first so it can not see itself as defined.
                     let begin_list = vec![
                         CoreSymbol::Begin.into(),
-                        set_is_def.into(),
                         set_field.into(),
+                        set_is_def.into(),
                     ];
 
                     compile_one(begin_list.into(), state)
@@ -17,12 +17,16 @@
     along with scheme-oxide.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::fmt;
+use std::path::Path;
+
 use crate::ast::{AstNode, AstSymbol};
 
 #[derive(Debug)]
 enum CompilerErrorType {
     ArgError,
     IdentifierNotFound,
+    IncludeError,
     SyntaxError,
     WrongType,
 }
@@ -55,6 +59,13 @@ impl CompilerError {
         }
     }
 
+    pub fn include(path: &Path, reason: &str) -> Self {
+        Self {
+            e_type: CompilerErrorType::IncludeError,
+            message: format!("Could not include {}: {}.", path.display(), reason),
+        }
+    }
+
     fn wrong_type(what: &str, expected: &str, got: &str) -> Self {
         Self {
             e_type: CompilerErrorType::WrongType,
@@ -63,6 +74,12 @@ impl CompilerError {
     }
 }
 
+impl fmt::Display for CompilerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
 pub trait AstCastErrorImpl {
     type CastExpected;
 
@@ -77,6 +94,14 @@ impl AstCastErrorImpl for Result<AstSymbol, AstNode> {
     }
 }
 
+impl AstCastErrorImpl for Result<String, AstNode> {
+    type CastExpected = String;
+
+    fn into_compiler_result(self, what: &str) -> Result<String, CompilerError> {
+        self.map_err(|err| CompilerError::wrong_type(what, "string", err.get_name()))
+    }
+}
+
 impl AstCastErrorImpl for Result<Vec<AstNode>, AstNode> {
     type CastExpected = Vec<AstNode>;
 
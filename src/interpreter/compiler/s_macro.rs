@@ -21,13 +21,14 @@ use crate::ast::{AstList, AstNode, AstSymbol, CoreSymbol};
 use crate::interpreter::vm::{Statement, StatementType};
 
 use super::{
-    compile_one, error::AstCastErrorImpl, CompilerAction, CompilerError, CompilerState,
-    CompilerType, LambdaBuilder, LetDef, PartialFunction,
+    compile_one, error::AstCastErrorImpl, expand_includes, expand_internal_defines, is_define,
+    CompilerAction, CompilerError, CompilerState, CompilerType, LambdaBuilder, LetDef,
+    PartialFunction,
 };
 
 #[derive(Clone, Debug)]
 pub enum BuiltinMacro {
-    Lambda { is_stage_1: bool },
+    Lambda { is_stage_1: bool, is_named: bool },
     If,
     Set,
     Begin,
@@ -36,9 +37,18 @@ pub enum BuiltinMacro {
     Let,
     LetStar,
     LetRec,
+    LetRecStar,
     Or,
     And,
     Cond,
+    Guard,
+    FluidLet,
+    When,
+    Unless,
+    Dotimes,
+    Delay,
+    DelayForce,
+    ConsStream,
     BeginProgram,
 }
 
@@ -52,8 +62,8 @@ fn assert_args(
         let or_more = if is_vargs { " or more" } else { "" };
 
         Err(CompilerError::argc(
-            &format!("{}{}", argc, or_more),
             what,
+            &format!("{}{}", argc, or_more),
             args.len(),
         ))
     } else {
@@ -61,6 +71,58 @@ fn assert_args(
     }
 }
 
+//Generates the hidden variables letrec and letrec* use to detect reads of a binding
+//before it is initialized.
+fn gen_undef_bindings(defs: &[LetDef]) -> (Vec<(AstSymbol, CompilerType)>, Vec<LetDef>) {
+    let mut undef_macros = Vec::new();
+    let mut undef_defs = Vec::new();
+
+    for def in defs {
+        let undef_field = AstSymbol::gen_temp();
+        let is_def = AstSymbol::gen_temp();
+
+        let maybe_undef = CompilerType::MaybeUndef {
+            field: undef_field.clone(),
+            is_def: is_def.clone(),
+        };
+
+        undef_macros.push((def.formal.clone(), maybe_undef));
+
+        undef_defs.push(LetDef {
+            formal: undef_field,
+            binding: AstNode::from_bool(false),
+        });
+
+        undef_defs.push(LetDef {
+            formal: is_def,
+            binding: AstNode::from_bool(false),
+        });
+    }
+
+    (undef_macros, undef_defs)
+}
+
+//Generates the promise for delay if is_delay is set and for delay-force otherwise.
+fn gen_promise(mut body: AstNode, is_delay: bool) -> AstNode {
+    //delay is delay-force of a promise that is already done.
+    if is_delay {
+        body = vec![
+            CoreSymbol::MakePromise.into(),
+            AstNode::from_bool(true),
+            body,
+        ]
+        .into();
+    }
+
+    let thunk = vec![CoreSymbol::Lambda.into(), AstList::none().into(), body];
+    vec![
+        CoreSymbol::MakePromise.into(),
+        AstNode::from_bool(false),
+        thunk.into(),
+    ]
+    .into()
+}
+
 impl BuiltinMacro {
     pub fn expand(
         &self,
@@ -69,11 +131,27 @@ impl BuiltinMacro {
         state: CompilerState,
     ) -> Result<Vec<CompilerAction>, CompilerError> {
         match self {
-            BuiltinMacro::Lambda { is_stage_1 } => {
+            BuiltinMacro::Lambda {
+                is_stage_1,
+                is_named,
+            } => {
+                let name = if *is_named {
+                    assert_args("$named-lambda", &args, 3, true)?;
+
+                    let name = args.remove(0);
+                    Some(name.into_symbol().into_compiler_result("$named-lambda")?)
+                } else {
+                    None
+                };
+
                 assert_args("lambda", &args, 2, true)?;
 
                 let raw_formal_list = args.remove(0);
-                let mut lambda_builder = LambdaBuilder::from_body_exprs(args, state)?;
+                let mut lambda_builder = LambdaBuilder::from_body(args, function, state)?;
+
+                if let Some(name) = name {
+                    lambda_builder.set_name(name)
+                }
 
                 let parsed_res = raw_formal_list
                     .into_list()
@@ -215,7 +293,7 @@ impl BuiltinMacro {
                     }
                 };
 
-                let defs = LetDef::from_raw_let(definitions)?;
+                let defs = LetDef::from_raw_let(definitions, "let")?;
 
                 match self_name {
                     Some(name) => {
@@ -239,7 +317,7 @@ impl BuiltinMacro {
                         compile_one(outer_binding, state)
                     }
                     None => {
-                        let lambda_builder = LambdaBuilder::from_body_exprs(args, state)?;
+                        let lambda_builder = LambdaBuilder::from_body(args, function, state)?;
                         lambda_builder.build_using_letdefs(defs)
                     }
                 }
@@ -278,33 +356,12 @@ impl BuiltinMacro {
                     .into_proper_list()
                     .into_compiler_result("letrec")?;
 
-                let in_defs = LetDef::from_raw_let(raw_defs)?;
-                let mut undef_macros = Vec::new();
-                let mut undef_defs = Vec::new();
+                let in_defs = LetDef::from_raw_let(raw_defs, "letrec")?;
+                let (undef_macros, undef_defs) = gen_undef_bindings(&in_defs);
                 let mut list_of_sets = Vec::new();
                 let mut tmp_bindings = Vec::new();
 
                 for def in in_defs {
-                    let undef_field = AstSymbol::gen_temp();
-                    let is_def = AstSymbol::gen_temp();
-
-                    let maybe_undef = CompilerType::MaybeUndef {
-                        field: undef_field.clone(),
-                        is_def: is_def.clone(),
-                    };
-
-                    undef_macros.push((def.formal.clone(), maybe_undef));
-
-                    undef_defs.push(LetDef {
-                        formal: undef_field,
-                        binding: AstNode::from_bool(false),
-                    });
-
-                    undef_defs.push(LetDef {
-                        formal: is_def,
-                        binding: AstNode::from_bool(false),
-                    });
-
                     let tmp_field = AstSymbol::gen_temp();
                     tmp_bindings.push(LetDef {
                         formal: tmp_field.clone(),
@@ -320,7 +377,8 @@ impl BuiltinMacro {
                     LambdaBuilder::from_body_exprs(list_of_sets, CompilerState::Body)?;
                 let mut tmp_scope = tmp_scope_builder.build_using_letdefs(tmp_bindings)?;
 
-                let in_code_builder = LambdaBuilder::from_body_exprs(args, CompilerState::Tail)?;
+                let in_code_builder =
+                    LambdaBuilder::from_body(args, function, CompilerState::Tail)?;
                 let in_code = in_code_builder.build_with_call(Vec::new())?;
 
                 let mut outer_body = in_code;
@@ -330,6 +388,28 @@ impl BuiltinMacro {
                 outer_scope_builder.add_macros(undef_macros);
                 outer_scope_builder.build_using_letdefs(undef_defs)
             }
+            BuiltinMacro::LetRecStar => {
+                assert_args("letrec*", &args, 2, true)?;
+
+                let raw_defs = args
+                    .remove(0)
+                    .into_proper_list()
+                    .into_compiler_result("letrec*")?;
+
+                let in_defs = LetDef::from_raw_let(raw_defs, "letrec*")?;
+                let (undef_macros, undef_defs) = gen_undef_bindings(&in_defs);
+
+                //Unlike letrec each binding is set as soon as it is evaluated.
+                let mut body: Vec<AstNode> = in_defs
+                    .into_iter()
+                    .map(|def| vec![CoreSymbol::Set.into(), def.formal.into(), def.binding].into())
+                    .collect();
+                body.append(&mut expand_internal_defines(args, function)?);
+
+                let mut scope_builder = LambdaBuilder::from_body_exprs(body, state)?;
+                scope_builder.add_macros(undef_macros);
+                scope_builder.build_using_letdefs(undef_defs)
+            }
             BuiltinMacro::And => {
                 let expr = if args.is_empty() {
                     AstNode::from_bool(true)
@@ -403,6 +483,9 @@ impl BuiltinMacro {
                     }
                 }
 
+                let arrow_symbol = AstSymbol::new("=>");
+                let is_arrow_bound = function.is_bounded(&arrow_symbol);
+
                 for raw_clause in args_iter {
                     let mut clause = raw_clause.into_proper_list().into_compiler_result("cond")?;
 
@@ -412,7 +495,24 @@ impl BuiltinMacro {
 
                     let test = clause.remove(0);
 
-                    let new_else_clause = if clause.is_empty() {
+                    let new_else_clause = if clause.len() == 2
+                        && clause[0] == arrow_symbol.clone().into()
+                        && !is_arrow_bound
+                    {
+                        //The receiver is called in the same position as the cond itself
+                        //so it is a tail call when the cond is in tail position.
+                        let receiver = clause.pop().unwrap();
+                        let test_res = AstSymbol::gen_temp();
+                        let bindings = vec![vec![test_res.clone().into(), test].into()];
+                        let receiver_call = vec![receiver, test_res.clone().into()];
+                        let if_list = vec![
+                            CoreSymbol::If.into(),
+                            test_res.into(),
+                            receiver_call.into(),
+                            else_clause.into(),
+                        ];
+                        vec![CoreSymbol::Let.into(), bindings.into(), if_list.into()]
+                    } else if clause.is_empty() {
                         let test_res = AstSymbol::gen_temp();
                         let bindings = vec![vec![test_res.clone().into(), test].into()];
                         let if_list = vec![
@@ -438,16 +538,230 @@ impl BuiltinMacro {
 
                 compile_one(else_clause.into(), state)
             }
+            BuiltinMacro::Guard => {
+                assert_args("guard", &args, 2, true)?;
+
+                let mut clauses = args
+                    .remove(0)
+                    .into_proper_list()
+                    .into_compiler_result("guard")?;
+                if clauses.is_empty() {
+                    return Err(CompilerError::syntax(
+                        "guard needs a variable to bind the condition to.",
+                    ));
+                }
+                let var = clauses
+                    .remove(0)
+                    .into_symbol()
+                    .into_compiler_result("guard")?;
+
+                //Without an else clause, conditions no clause matches are raised again.
+                let else_symbol = AstSymbol::new("else");
+                let has_else = clauses
+                    .last()
+                    .and_then(AstNode::as_proper_list)
+                    .and_then(|clause| clause.first())
+                    .map(|test| *test == else_symbol.clone().into())
+                    .unwrap_or(false)
+                    && !function.is_bounded(&else_symbol);
+                if !has_else {
+                    let reraise = vec![CoreSymbol::Raise.into(), var.clone().into()];
+                    clauses.push(vec![AstNode::from_bool(true), reraise.into()].into());
+                }
+
+                let mut cond_list = vec![CoreSymbol::Cond.into()];
+                cond_list.append(&mut clauses);
+                let handler = vec![
+                    CoreSymbol::Lambda.into(),
+                    vec![var.into()].into(),
+                    cond_list.into(),
+                ];
+
+                let mut thunk = vec![CoreSymbol::Lambda.into(), AstList::none().into()];
+                thunk.append(&mut args);
+
+                let guard_call = vec![CoreSymbol::Guard.into(), thunk.into(), handler.into()];
+                compile_one(guard_call.into(), state)
+            }
+            BuiltinMacro::FluidLet => {
+                assert_args("fluid-let", &args, 2, true)?;
+
+                let raw_defs = args
+                    .remove(0)
+                    .into_proper_list()
+                    .into_compiler_result("fluid-let")?;
+
+                let defs = LetDef::from_raw_let(raw_defs, "fluid-let")?;
+                let mut new_value_bindings = Vec::new();
+                let mut swap_body = vec![CoreSymbol::Begin.into()];
+
+                //Swapping the variables with the new values both installs the new values and
+                //later puts the originals back.
+                for def in defs {
+                    let new_value = AstSymbol::gen_temp();
+                    let old_value = AstSymbol::gen_temp();
+                    new_value_bindings.push(vec![new_value.clone().into(), def.binding].into());
+
+                    let set_var = vec![
+                        CoreSymbol::Set.into(),
+                        def.formal.clone().into(),
+                        new_value.clone().into(),
+                    ];
+                    let set_new_value = vec![
+                        CoreSymbol::Set.into(),
+                        new_value.into(),
+                        old_value.clone().into(),
+                    ];
+                    swap_body.push(
+                        vec![
+                            CoreSymbol::Let.into(),
+                            vec![vec![old_value.into(), def.formal.into()].into()].into(),
+                            set_var.into(),
+                            set_new_value.into(),
+                        ]
+                        .into(),
+                    );
+                }
+
+                let swap = AstSymbol::gen_temp();
+                let swap_fn = vec![
+                    CoreSymbol::Lambda.into(),
+                    AstList::none().into(),
+                    swap_body.into(),
+                ];
+
+                let mut body = vec![CoreSymbol::Lambda.into(), AstList::none().into()];
+                body.append(&mut args);
+
+                //The originals are restored even if the body is escaped by a raise.
+                let protected_body = vec![
+                    CoreSymbol::UnwindProtect.into(),
+                    body.into(),
+                    swap.clone().into(),
+                ];
+                let swap_let = vec![
+                    CoreSymbol::Let.into(),
+                    vec![vec![swap.clone().into(), swap_fn.into()].into()].into(),
+                    vec![swap.into()].into(),
+                    protected_body.into(),
+                ];
+                let let_list = vec![
+                    CoreSymbol::Let.into(),
+                    new_value_bindings.into(),
+                    swap_let.into(),
+                ];
+
+                compile_one(let_list.into(), state)
+            }
+            BuiltinMacro::When | BuiltinMacro::Unless => {
+                let (name, is_when) = match self {
+                    BuiltinMacro::When => ("when", true),
+                    _ => ("unless", false),
+                };
+                assert_args(name, &args, 2, true)?;
+
+                let test = args.remove(0);
+                let mut body = vec![CoreSymbol::Begin.into()];
+                body.append(&mut args);
+
+                //The body's last value is returned; a skipped body is unspecified.
+                let skipped = vec![CoreSymbol::GenUnspecified.into()];
+                let if_list = if is_when {
+                    vec![CoreSymbol::If.into(), test, body.into(), skipped.into()]
+                } else {
+                    vec![CoreSymbol::If.into(), test, skipped.into(), body.into()]
+                };
+
+                compile_one(if_list.into(), state)
+            }
+            BuiltinMacro::Dotimes => {
+                assert_args("dotimes", &args, 1, true)?;
+
+                let mut spec = args
+                    .remove(0)
+                    .into_proper_list()
+                    .into_compiler_result("dotimes")?;
+                if spec.len() != 2 {
+                    return Err(CompilerError::syntax(
+                        "dotimes expects a variable and a count.",
+                    ));
+                }
+                let count = spec.pop().unwrap();
+                let var = spec
+                    .pop()
+                    .unwrap()
+                    .into_symbol()
+                    .into_compiler_result("dotimes")?;
+
+                let limit = AstSymbol::gen_temp();
+                let loop_name = AstSymbol::gen_temp();
+
+                let next = vec![
+                    CoreSymbol::Add.into(),
+                    var.clone().into(),
+                    AstNode::from_number(1),
+                ];
+                let mut body = vec![CoreSymbol::Begin.into()];
+                body.append(&mut args);
+                body.push(vec![loop_name.clone().into(), next.into()].into());
+
+                let test = vec![
+                    CoreSymbol::Less.into(),
+                    var.clone().into(),
+                    limit.clone().into(),
+                ];
+                let if_list = vec![CoreSymbol::If.into(), test.into(), body.into()];
+                let loop_list = vec![
+                    CoreSymbol::Let.into(),
+                    loop_name.into(),
+                    vec![vec![var.into(), AstNode::from_number(0)].into()].into(),
+                    if_list.into(),
+                ];
+                let let_list = vec![
+                    CoreSymbol::Let.into(),
+                    vec![vec![limit.into(), count].into()].into(),
+                    loop_list.into(),
+                ];
+
+                compile_one(let_list.into(), state)
+            }
+            BuiltinMacro::Delay | BuiltinMacro::DelayForce => {
+                let (name, is_delay) = match self {
+                    BuiltinMacro::Delay => ("delay", true),
+                    _ => ("delay-force", false),
+                };
+                assert_args(name, &args, 1, false)?;
+
+                compile_one(gen_promise(args.pop().unwrap(), is_delay), state)
+            }
+            BuiltinMacro::ConsStream => {
+                assert_args("cons-stream", &args, 2, false)?;
+
+                let tail = gen_promise(args.pop().unwrap(), true);
+                let head = args.pop().unwrap();
+
+                compile_one(vec![CoreSymbol::Cons.into(), head, tail].into(), state)
+            }
             BuiltinMacro::BeginProgram => {
                 assert_args("$begin-program", &args, 1, false)?;
 
-                let code = args
+                let mut code = args
                     .pop()
                     .unwrap()
                     .into_proper_list()
                     .into_compiler_result("$begin-program")?;
+                code = expand_includes(code, function)?;
+
+                //A program that is empty or ends with a definition has no value of its own.
+                let has_value = match code.last() {
+                    Some(last) => !is_define(last),
+                    None => false,
+                };
+                if !has_value {
+                    code.push(vec![CoreSymbol::GenUnspecified.into()].into());
+                }
 
-                let lambda_builder = LambdaBuilder::from_body_exprs(code, state)?;
+                let lambda_builder = LambdaBuilder::from_body(code, function, state)?;
 
                 lambda_builder.build_using_letdefs(function.environment.map.iter().filter_map(
                     |(var, value)| match value {
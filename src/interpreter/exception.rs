@@ -0,0 +1,146 @@
+/*
+    Copyright 2019 Alexander Eckhart
+
+    This file is part of scheme-oxide.
+
+    Scheme-oxide is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Scheme-oxide is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with scheme-oxide.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::cell::RefCell;
+
+use crate::environment;
+use crate::types::*;
+
+use super::{FunctionRef, RuntimeError};
+
+//Handlers installed by with-exception-handler, innermost last.
+//None marks a guard, which catches the raise by unwinding to it.
+thread_local! {
+    static HANDLERS: RefCell<Vec<Option<FunctionRef>>> = const { RefCell::new(Vec::new()) };
+}
+
+fn handler_count() -> usize {
+    HANDLERS.with(|handlers| handlers.borrow().len())
+}
+
+fn push_handler(handler: Option<FunctionRef>) {
+    HANDLERS.with(|handlers| handlers.borrow_mut().push(handler))
+}
+
+fn truncate_handlers(len: usize) -> Vec<Option<FunctionRef>> {
+    HANDLERS.with(|handlers| handlers.borrow_mut().split_off(len))
+}
+
+fn restore_handlers(mut removed: Vec<Option<FunctionRef>>) {
+    HANDLERS.with(|handlers| handlers.borrow_mut().append(&mut removed))
+}
+
+//Runs f with the handler stack cut down to len then puts the removed handlers back,
+//even if f fails.
+fn with_handler_count<F>(len: usize, f: F) -> Result<SchemeType, RuntimeError>
+where
+    F: FnOnce() -> Result<SchemeType, RuntimeError>,
+{
+    let removed = truncate_handlers(len);
+    let ret = f();
+    truncate_handlers(len);
+    restore_handlers(removed);
+    ret
+}
+
+//Errors from builtins are raised to handler once they reach here, as if by raise.
+pub fn with_exception_handler(
+    handler: FunctionRef,
+    thunk: FunctionRef,
+) -> Result<SchemeType, RuntimeError> {
+    let len = handler_count();
+    with_handler_count(len, || {
+        push_handler(Some(handler));
+        match thunk.call(Vec::new()) {
+            Err(RuntimeError::Raise(obj)) => Err(RuntimeError::Raise(obj)),
+            Err(err) => raise(error_to_condition(&err)),
+            result => result,
+        }
+    })
+}
+
+//Returns Err(RuntimeError::Raise) if obj was raised within thunk and not handled on the way.
+//Errors from builtins come out as raises of error objects.
+pub fn guard(thunk: FunctionRef) -> Result<SchemeType, RuntimeError> {
+    let len = handler_count();
+    with_handler_count(len, || {
+        push_handler(None);
+        thunk.call(Vec::new()).map_err(|err| match err {
+            RuntimeError::Raise(obj) => RuntimeError::Raise(obj),
+            err => RuntimeError::Raise(error_to_condition(&err)),
+        })
+    })
+}
+
+//An error object with no who or irritants, whose message describes err.
+fn error_to_condition(err: &RuntimeError) -> SchemeType {
+    let message: SchemeString = err.to_string().parse().unwrap();
+    new_error_object(environment::s_false(), message.into(), Vec::new())
+}
+
+pub fn raise(obj: SchemeType) -> Result<SchemeType, RuntimeError> {
+    raise_with(obj, false)
+}
+
+//The value the handler returns becomes the value of the raise.
+pub fn raise_continuable(obj: SchemeType) -> Result<SchemeType, RuntimeError> {
+    raise_with(obj, true)
+}
+
+fn raise_with(obj: SchemeType, is_continuable: bool) -> Result<SchemeType, RuntimeError> {
+    let len = handler_count();
+    let handler = match HANDLERS.with(|handlers| handlers.borrow().last().cloned()) {
+        Some(Some(handler)) => handler,
+        _ => return Err(RuntimeError::Raise(obj)),
+    };
+
+    //The handler is called with the outer handlers installed.
+    with_handler_count(len - 1, || {
+        let ret = handler.call(vec![obj.clone()])?;
+        if is_continuable {
+            return Ok(ret);
+        }
+
+        let message: SchemeString = "Handler returned from a non-continuable raise."
+            .parse()
+            .unwrap();
+        raise(new_error_object(
+            new_symbol("raise".to_string()).into(),
+            message.into(),
+            vec![obj],
+        ))
+    })
+}
+
+pub fn new_error_object(
+    who: SchemeType,
+    message: SchemeType,
+    irritants: Vec<SchemeType>,
+) -> SchemeType {
+    let mut irritant_list = ListFactory::new(true);
+    for irritant in irritants {
+        irritant_list.push(irritant)
+    }
+
+    SchemeObject::new(
+        environment::error_object_type_id(),
+        vec![who, message, irritant_list.build()],
+    )
+    .into()
+}
@@ -0,0 +1,67 @@
+/*
+    Copyright 2019 Alexander Eckhart
+
+    This file is part of scheme-oxide.
+
+    Scheme-oxide is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Scheme-oxide is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with scheme-oxide.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+thread_local! {
+    //Kept separate from the counts so a disabled profiler only costs a flag check per call.
+    static ENABLED: Cell<bool> = const { Cell::new(false) };
+    static CALL_COUNTS: RefCell<HashMap<String, u64>> = RefCell::new(HashMap::new());
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.with(Cell::get)
+}
+
+//Clears counts left over from any earlier profile.
+pub fn start() {
+    CALL_COUNTS.with(|counts| counts.borrow_mut().clear());
+    ENABLED.with(|enabled| enabled.set(true))
+}
+
+pub fn stop() {
+    ENABLED.with(|enabled| enabled.set(false))
+}
+
+pub fn record_call(name: &str) {
+    CALL_COUNTS.with(|counts| {
+        let mut counts = counts.borrow_mut();
+        if let Some(count) = counts.get_mut(name) {
+            *count += 1
+        } else {
+            counts.insert(name.to_string(), 1);
+        }
+    })
+}
+
+//Most called first, ties broken by name.
+pub fn report() -> Vec<(String, u64)> {
+    let mut report: Vec<_> = CALL_COUNTS.with(|counts| {
+        counts
+            .borrow()
+            .iter()
+            .map(|(name, count)| (name.clone(), *count))
+            .collect()
+    });
+    report.sort_by(|(x_name, x_count), (y_name, y_count)| {
+        y_count.cmp(x_count).then_with(|| x_name.cmp(y_name))
+    });
+    report
+}
@@ -22,7 +22,8 @@ use std::cmp::Ordering;
 use std::rc::Rc;
 
 use crate::ast::{AstNode, AstSymbol, CoreSymbol};
-use crate::parser::Parser;
+use crate::environment;
+use crate::parser::{Parser, Span};
 use crate::types::*;
 
 use super::{
@@ -74,18 +75,27 @@ impl BaseEnvironment {
         Ok(())
     }
 
-    fn push_lib_file(&mut self, file: &str) -> Result<(), RuntimeError> {
-        let define_symbol = AstSymbol::new("define");
-        for raw_statement in Parser::new(file) {
-            let mut statement = raw_statement.unwrap().into_proper_list().unwrap();
-
-            if let Some(true) = statement[0].as_symbol().map(|x| *x == define_symbol) {
-                statement.remove(0);
-                let (symbol, expr) = parse_define(statement)?;
-                self.push_eval(symbol, expr)?
-            } else {
-                self.eval(statement.into())?;
-            }
+    fn push_lib_statement(&mut self, mut statement: Vec<AstNode>) -> Result<(), RuntimeError> {
+        if let Some(true) = statement[0]
+            .as_symbol()
+            .map(|x| *x == AstSymbol::new("define"))
+        {
+            statement.remove(0);
+            let (symbol, expr) = parse_define(statement)?;
+            self.push_eval(symbol, expr)
+        } else {
+            self.eval(statement.into()).map(|_| ())
+        }
+    }
+
+    //Errors come with the span of the statement that caused them.
+    fn push_lib_file(&mut self, file: &str) -> Result<(), (Span, RuntimeError)> {
+        let mut parser = Parser::new(file);
+        while let Some(raw_statement) = parser.next_with_spans() {
+            let (statement, spans) = raw_statement.unwrap();
+
+            self.push_lib_statement(statement.into_proper_list().unwrap())
+                .map_err(|err| (spans.span, err))?;
         }
         Ok(())
     }
@@ -100,6 +110,7 @@ fn gen_stage0_environment() -> BaseEnvironment {
     ret.push_object(AstSymbol::new("$newline-str"), newline_str.into());
 
     ret.push_builtin_function(AstSymbol::new("+"), BuiltinFunction::Add);
+    ret.push_builtin_function(CoreSymbol::Add.into(), BuiltinFunction::Add);
     ret.push_builtin_function(AstSymbol::new("*"), BuiltinFunction::Mul);
     ret.push_builtin_function(AstSymbol::new("-"), BuiltinFunction::Sub);
 
@@ -117,6 +128,13 @@ fn gen_stage0_environment() -> BaseEnvironment {
             mode: Ordering::Less,
         },
     );
+    ret.push_builtin_function(
+        CoreSymbol::Less.into(),
+        BuiltinFunction::Compare {
+            invert: false,
+            mode: Ordering::Less,
+        },
+    );
     ret.push_builtin_function(
         AstSymbol::new("<="),
         BuiltinFunction::Compare {
@@ -148,6 +166,10 @@ fn gen_stage0_environment() -> BaseEnvironment {
         AstSymbol::new("$object-field-get"),
         BuiltinFunction::GetField,
     );
+    ret.push_builtin_function(
+        AstSymbol::new("$object-field-count"),
+        BuiltinFunction::FieldCount,
+    );
     ret.push_builtin_function(
         AstSymbol::new("$object-field-set!"),
         BuiltinFunction::SetField,
@@ -156,8 +178,57 @@ fn gen_stage0_environment() -> BaseEnvironment {
     ret.push_builtin_function(AstSymbol::new("eqv?"), BuiltinFunction::Eqv);
     ret.push_builtin_function(AstSymbol::new("quotient"), BuiltinFunction::Quotient);
     ret.push_builtin_function(AstSymbol::new("remainder"), BuiltinFunction::Remainder);
+    ret.push_builtin_function(
+        AstSymbol::new("truncate-quotient"),
+        BuiltinFunction::Quotient,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("truncate-remainder"),
+        BuiltinFunction::Remainder,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("floor-quotient"),
+        BuiltinFunction::FloorQuotient,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("floor-remainder"),
+        BuiltinFunction::FloorRemainder,
+    );
+    ret.push_builtin_function(AstSymbol::new("modulo"), BuiltinFunction::FloorRemainder);
+    ret.push_builtin_function(AstSymbol::new("modexp"), BuiltinFunction::ModExp);
     ret.push_builtin_function(AstSymbol::new("error"), BuiltinFunction::Error);
     ret.push_builtin_function(CoreSymbol::Error.into(), BuiltinFunction::Error);
+    ret.push_builtin_function(AstSymbol::new("raise"), BuiltinFunction::Raise);
+    ret.push_builtin_function(CoreSymbol::Raise.into(), BuiltinFunction::Raise);
+    ret.push_builtin_function(
+        AstSymbol::new("raise-continuable"),
+        BuiltinFunction::RaiseContinuable,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("with-exception-handler"),
+        BuiltinFunction::WithExceptionHandler,
+    );
+    ret.push_builtin_function(CoreSymbol::Guard.into(), BuiltinFunction::Guard);
+    ret.push_builtin_function(
+        CoreSymbol::UnwindProtect.into(),
+        BuiltinFunction::UnwindProtect,
+    );
+    ret.push_builtin_function(AstSymbol::new("dynamic-wind"), BuiltinFunction::DynamicWind);
+    ret.push_object(
+        AstSymbol::new("$error-object-type-id"),
+        environment::error_object_type_id(),
+    );
+    ret.push_object(
+        AstSymbol::new("$promise-type-id"),
+        environment::promise_type_id(),
+    );
+    ret.push_builtin_function(CoreSymbol::MakePromise.into(), BuiltinFunction::MakePromise);
+    ret.push_builtin_function(
+        AstSymbol::new("$make-promise"),
+        BuiltinFunction::MakePromise,
+    );
+    //Only for macros, cons itself is defined in stage1.
+    ret.push_builtin_function(CoreSymbol::Cons.into(), BuiltinFunction::Cons);
 
     ret.push_builtin_function(
         CoreSymbol::GenUnspecified.into(),
@@ -171,7 +242,101 @@ fn gen_stage0_environment() -> BaseEnvironment {
     ret.push_builtin_function(AstSymbol::new("number?"), BuiltinFunction::IsNumber);
     ret.push_builtin_function(AstSymbol::new("char?"), BuiltinFunction::IsChar);
     ret.push_builtin_function(AstSymbol::new("string?"), BuiltinFunction::IsString);
+    ret.push_builtin_function(AstSymbol::new("procedure?"), BuiltinFunction::IsProcedure);
+    ret.push_builtin_function(AstSymbol::new("bytevector?"), BuiltinFunction::IsBytevector);
+    ret.push_builtin_function(
+        AstSymbol::new("exact-nonnegative-integer?"),
+        BuiltinFunction::IsExactNonnegativeInteger,
+    );
+    ret.push_builtin_function(AstSymbol::new("bytevector"), BuiltinFunction::NewBytevector);
+    ret.push_builtin_function(
+        AstSymbol::new("make-bytevector"),
+        BuiltinFunction::MakeBytevector,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("bytevector-length"),
+        BuiltinFunction::BytevectorLen,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("bytevector-u8-ref"),
+        BuiltinFunction::GetByte,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("bytevector-u8-set!"),
+        BuiltinFunction::SetByte,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("string->utf8"),
+        BuiltinFunction::StringToUtf8,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("utf8->string"),
+        BuiltinFunction::Utf8ToString,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("$procedure-name"),
+        BuiltinFunction::ProcedureName,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("procedure-arity"),
+        BuiltinFunction::ProcedureArity,
+    );
     ret.push_builtin_function(AstSymbol::new("write-char"), BuiltinFunction::WriteChar);
+    ret.push_builtin_function(AstSymbol::new("apply"), BuiltinFunction::Apply);
+    ret.push_builtin_function(AstSymbol::new("eq-hash"), BuiltinFunction::EqvHash);
+    ret.push_builtin_function(AstSymbol::new("eqv-hash"), BuiltinFunction::EqvHash);
+    ret.push_builtin_function(AstSymbol::new("equal-hash"), BuiltinFunction::EqualHash);
+    ret.push_builtin_function(
+        AstSymbol::new("symbol-append"),
+        BuiltinFunction::SymbolAppend,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("symbol-interned?"),
+        BuiltinFunction::SymbolInterned,
+    );
+    ret.push_builtin_function(AstSymbol::new("gensym"), BuiltinFunction::Gensym);
+    ret.push_builtin_function(AstSymbol::new("char-upcase"), BuiltinFunction::CharUpcase);
+    ret.push_builtin_function(
+        AstSymbol::new("char-downcase"),
+        BuiltinFunction::CharDowncase,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("char-foldcase"),
+        BuiltinFunction::CharFoldcase,
+    );
+    ret.push_builtin_function(AstSymbol::new("digit-value"), BuiltinFunction::DigitValue);
+    ret.push_builtin_function(
+        AstSymbol::new("char->integer"),
+        BuiltinFunction::CharToInteger,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("integer->char"),
+        BuiltinFunction::IntegerToChar,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("string-foldcase"),
+        BuiltinFunction::StringFoldcase,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("profile-start!"),
+        BuiltinFunction::ProfileStart,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("profile-stop!"),
+        BuiltinFunction::ProfileStop,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("profile-report"),
+        BuiltinFunction::ProfileReport,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("current-jiffy"),
+        BuiltinFunction::CurrentJiffy,
+    );
+    ret.push_builtin_function(
+        AstSymbol::new("jiffies-per-second"),
+        BuiltinFunction::JiffiesPerSecond,
+    );
 
     ret
 }
@@ -180,17 +345,25 @@ fn gen_stage1_environment() -> BaseEnvironment {
     let mut ret = gen_stage0_environment();
 
     ret.push_lib_file(include_str!("../../scheme-src/stage1.scm"))
-        .unwrap();
+        .unwrap_or_else(|(span, err)| panic!("stage1.scm:{}: {:?}", span, err));
 
     ret
 }
 
-fn gen_scheme_environment() -> BaseEnvironment {
+//Has the builtins, the pair procedures from stage1 and every syntax form, but none of std-lib.
+fn gen_minimal_environment() -> BaseEnvironment {
     let mut ret = STAGE1_ENVIRONMENT.with(Clone::clone);
 
     ret.frame.add_stage2_macros();
+
+    ret
+}
+
+fn gen_scheme_environment() -> BaseEnvironment {
+    let mut ret = MINIMAL_ENVIRONMENT.with(Clone::clone);
+
     ret.push_lib_file(include_str!("../../scheme-src/std-lib.scm"))
-        .unwrap();
+        .unwrap_or_else(|(span, err)| panic!("std-lib.scm:{}: {:?}", span, err));
 
     ret
 }
@@ -198,5 +371,7 @@ fn gen_scheme_environment() -> BaseEnvironment {
 thread_local! {
     pub static STAGE1_ENVIRONMENT: BaseEnvironment = gen_stage1_environment();
 
+    pub static MINIMAL_ENVIRONMENT: BaseEnvironment = gen_minimal_environment();
+
     pub static SCHEME_ENVIRONMENT: BaseEnvironment = gen_scheme_environment();
 }
@@ -22,7 +22,7 @@ use std::rc::Rc;
 
 use crate::types::*;
 
-use super::{DerivedFunctionRef, FunctionRef, FunctionRefInner, RuntimeError};
+use super::{to_procedure, DerivedFunctionRef, FunctionRef, FunctionRefInner, RuntimeError};
 
 #[derive(Copy, Clone, Debug)]
 pub struct Statement {
@@ -61,6 +61,7 @@ impl StackFrame {
 
 #[derive(Clone, Debug, Default)]
 pub struct SchemeFunction {
+    name: Option<String>,
     args: u32,
     is_vargs: bool,
     captures: Vec<u32>,
@@ -72,6 +73,7 @@ pub struct SchemeFunction {
 impl SchemeFunction {
     pub fn new(argc: u32, is_vargs: bool) -> Self {
         Self {
+            name: None,
             args: argc,
             is_vargs,
             captures: Vec::new(),
@@ -81,6 +83,14 @@ impl SchemeFunction {
         }
     }
 
+    pub fn set_name(&mut self, name: String) {
+        self.name = Some(name)
+    }
+
+    pub fn get_name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
     pub fn append_code(&mut self, mut code: Vec<Statement>) {
         self.code.append(&mut code)
     }
@@ -143,8 +153,7 @@ pub fn run_vm(mut stack: Vec<StackFrame>) -> Result<SchemeType, RuntimeError> {
                         });
                     }
 
-                    let ret_expr = new_function
-                        .to_function()?
+                    let ret_expr = to_procedure(&new_function)?
                         .0
                         .call_with_stack(&mut stack, args)?;
 
@@ -20,6 +20,8 @@
 use std::env;
 use std::fs::File;
 use std::io::prelude::*;
+use std::path::Path;
+use std::process;
 
 use getopts::Options;
 
@@ -36,14 +38,19 @@ mod interpreter;
 mod tests;
 
 fn print_usage(name: &str) {
-    println!("Usage: {} PROGRAM", name)
+    println!("Usage: {} [--no-prelude] PROGRAM", name)
 }
 
 fn main() {
     let args = env::args().collect::<Vec<_>>();
     let self_name = args[0].clone();
 
-    let opts = Options::new();
+    let mut opts = Options::new();
+    opts.optflag(
+        "",
+        "no-prelude",
+        "run without the procedures from std-lib.scm",
+    );
     let matches = match opts.parse(&args[1..]) {
         Ok(m) => m,
         Err(e) => panic!(e.to_string()),
@@ -61,9 +68,27 @@ fn main() {
         return;
     };
 
-    let mut file = File::open(file_name).unwrap();
+    let mut file = File::open(&file_name).unwrap();
     let mut prog = String::new();
     file.read_to_string(&mut prog).unwrap();
 
-    println!("{:?}", interpreter::eval(&prog).unwrap());
+    //Relative includes in the program are resolved against the directory it is in.
+    let dir = Path::new(&file_name)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    let result = interpreter::with_include_dir(dir, || {
+        if matches.opt_present("no-prelude") {
+            interpreter::eval_without_prelude(&prog)
+        } else {
+            interpreter::eval(&prog)
+        }
+    });
+
+    match result {
+        Ok(value) => println!("{:?}", value),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            process::exit(1);
+        }
+    }
 }
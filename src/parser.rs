@@ -17,36 +17,69 @@
     along with scheme-oxide.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
 use crate::ast::{AstListBuilder, AstNode, AstSymbol};
 
-use self::tokenizer::{Block, Mark, Token, Tokenizer, TokenizerError};
+use self::tokenizer::{Block, Mark, Token, Tokenizer, TokenizerError, DEFAULT_MAX_TOKEN_LEN};
+
+pub use self::tokenizer::{Position, Span};
 
 mod tokenizer;
 
+#[cfg(test)]
+mod test;
+
+//Where a datum came from in the source. A list has one child for each element, with the tail
+//of an improper list last, and a vector or bytevector has one for each element.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceSpans {
+    pub span: Span,
+    pub children: Vec<SourceSpans>,
+}
+
+impl SourceSpans {
+    fn leaf(span: Span) -> Self {
+        SourceSpans {
+            span,
+            children: Vec::new(),
+        }
+    }
+}
+
 enum ParserToken {
-    PartialList(AstListBuilder),
-    ListEnd,
-    Datum(AstNode),
+    PartialList(AstListBuilder, Position, Vec<SourceSpans>),
+    ListEnd(Position),
+    Datum(AstNode, SourceSpans),
     Dot,
-    Mark(Mark),
+    Mark(Mark, Span),
 }
 
 impl ParserToken {
-    fn from_token(token: Token) -> Result<ParserToken, ParserError> {
-        Ok(match token {
-            Token::Block(Block::Start) => ParserToken::PartialList(AstListBuilder::new()),
-            Token::Block(Block::End) => ParserToken::ListEnd,
-            Token::TString(string) => {
-                ParserToken::Datum(AstNode::from_string(unescape_string(string)?))
+    fn from_token(token: Token, span: Span) -> Result<ParserToken, ParserError> {
+        let datum = match token {
+            Token::Block(Block::Start) => {
+                return Ok(ParserToken::PartialList(
+                    AstListBuilder::new(),
+                    span.start,
+                    Vec::new(),
+                ))
             }
-            Token::Symbol(symbol) => ParserToken::Datum(AstSymbol::new(symbol).into()),
-            Token::Number(num) => {
-                ParserToken::Datum(AstNode::from_number(i64::from_str_radix(num, 10)?))
-            }
-            Token::Bool(boolean) => ParserToken::Datum(AstNode::from_bool(boolean)),
-            Token::Dot => ParserToken::Dot,
-            Token::Mark(mark) => ParserToken::Mark(mark),
-        })
+            Token::Block(Block::End) => return Ok(ParserToken::ListEnd(span.end)),
+            Token::Dot => return Ok(ParserToken::Dot),
+            Token::Mark(mark) => return Ok(ParserToken::Mark(mark, span)),
+            Token::TString(string) => AstNode::from_string(unescape_string(string)?),
+            Token::Symbol(symbol) => AstSymbol::new(symbol).into(),
+            Token::Number(num) => AstNode::from_number(i64::from_str_radix(num, 10)?),
+            Token::Bool(boolean) => AstNode::from_bool(boolean),
+            Token::Char(c) => AstNode::from_char(c),
+            //The parser's tokenizer skips comments.
+            Token::Comment(_) => unreachable!(),
+        };
+
+        Ok(ParserToken::Datum(datum, SourceSpans::leaf(span)))
     }
 }
 
@@ -56,6 +89,10 @@ pub enum ParserError {
     NumberParse,
     Syntax,
     UnknownEscapeSequence,
+    ByteOutOfRange,
+    TooDeep,
+    //A ) with no ( before it to close, at the given position.
+    UnexpectedListEnd(Position),
 }
 
 impl From<TokenizerError> for ParserError {
@@ -64,15 +101,59 @@ impl From<TokenizerError> for ParserError {
     }
 }
 
+impl fmt::Display for ParserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParserError::TokenizerError(err) => write!(f, "{:?}.", err),
+            ParserError::NumberParse => write!(f, "Number is too large."),
+            ParserError::Syntax => write!(f, "Syntax error."),
+            ParserError::UnknownEscapeSequence => write!(f, "Unknown escape sequence in string."),
+            ParserError::ByteOutOfRange => write!(f, "Bytevector element is not a byte."),
+            ParserError::TooDeep => write!(f, "Lists are nested too deeply."),
+            ParserError::UnexpectedListEnd(position) => {
+                write!(f, "Unexpected ) at {}:{}.", position.line, position.column)
+            }
+        }
+    }
+}
+
 impl From<std::num::ParseIntError> for ParserError {
     fn from(_: std::num::ParseIntError) -> ParserError {
         ParserError::NumberParse
     }
 }
 
+fn is_intraline_whitespace(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+//Skips a \ followed by intraline whitespace, a line ending, and more intraline whitespace.
+//The backslash and the first character after it have already been consumed.
+fn skip_line_continuation(
+    first: char,
+    iterator: &mut Peekable<Chars<'_>>,
+) -> Result<(), ParserError> {
+    let mut current = first;
+    while is_intraline_whitespace(current) {
+        current = iterator.next().ok_or(ParserError::UnknownEscapeSequence)?;
+    }
+
+    match current {
+        '\n' => (),
+        '\r' => {
+            iterator.next_if_eq(&'\n');
+        }
+        _ => return Err(ParserError::UnknownEscapeSequence),
+    }
+
+    while iterator.next_if(|c| is_intraline_whitespace(*c)).is_some() {}
+
+    Ok(())
+}
+
 fn unescape_string(string: &str) -> Result<String, ParserError> {
     let mut new_string = String::new();
-    let mut iterator = string.chars();
+    let mut iterator = string.chars().peekable();
 
     while let Some(character) = iterator.next() {
         if character == '\\' {
@@ -80,6 +161,10 @@ fn unescape_string(string: &str) -> Result<String, ParserError> {
             let escaped_char = match escape {
                 '\\' => '\\',
                 '"' => '"',
+                c if is_intraline_whitespace(c) || c == '\n' || c == '\r' => {
+                    skip_line_continuation(c, &mut iterator)?;
+                    continue;
+                }
                 _ => return Err(ParserError::UnknownEscapeSequence),
             };
             new_string.push(escaped_char);
@@ -91,30 +176,58 @@ fn unescape_string(string: &str) -> Result<String, ParserError> {
     Ok(new_string)
 }
 
+//Deepest list nesting accepted by default.
+//Later passes walk the tree recursively so this keeps them from overflowing the stack.
+pub const DEFAULT_MAX_DEPTH: usize = 1024;
+
 pub struct Parser<'a> {
     stack: Vec<ParserToken>,
     tokenizer: Tokenizer<'a>,
+    depth: usize,
+    max_depth: usize,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(input: &'a str) -> Self {
+        Self::with_max_token_len(input, DEFAULT_MAX_TOKEN_LEN)
+    }
+
+    pub fn with_max_token_len(input: &'a str, max_token_len: usize) -> Self {
+        Self::with_limits(input, max_token_len, DEFAULT_MAX_DEPTH)
+    }
+
+    pub fn with_limits(input: &'a str, max_token_len: usize, max_depth: usize) -> Self {
         Parser {
             stack: Vec::new(),
-            tokenizer: Tokenizer::new(input),
+            tokenizer: Tokenizer::new(input, max_token_len, false),
+            depth: 0,
+            max_depth,
         }
     }
 
     //True if end of file
     fn push_input(&mut self) -> Result<bool, ParserError> {
         Ok(if let Some(token) = self.tokenizer.next().transpose()? {
-            self.stack.push(ParserToken::from_token(token)?);
+            let parser_token = ParserToken::from_token(token, self.tokenizer.token_span())?;
+            //A pending quote or datum comment nests the datum after it like a list does.
+            //Vector and bytevector marks don't count since their ( already does.
+            if let ParserToken::PartialList(..)
+            | ParserToken::Mark(Mark::Quote, _)
+            | ParserToken::Mark(Mark::DatumComment, _) = parser_token
+            {
+                if self.depth == self.max_depth {
+                    return Err(ParserError::TooDeep);
+                }
+                self.depth += 1;
+            }
+            self.stack.push(parser_token);
             false
         } else {
             true
         })
     }
 
-    fn iter_once(&mut self) -> Result<Option<AstNode>, ParserError> {
+    fn iter_once(&mut self) -> Result<Option<(AstNode, SourceSpans)>, ParserError> {
         loop {
             let stack_top = self.stack.pop();
             match stack_top {
@@ -123,16 +236,18 @@ impl<'a> Parser<'a> {
                         return Ok(None);
                     }
                 }
-                Some(ParserToken::Datum(datum)) => match self.stack.pop() {
-                    None => return Ok(Some(datum)),
-                    Some(ParserToken::PartialList(mut factory)) => {
+                Some(ParserToken::Datum(datum, spans)) => match self.stack.pop() {
+                    None => return Ok(Some((datum, spans))),
+                    Some(ParserToken::PartialList(mut factory, start, mut children)) => {
                         factory.push(datum);
-                        self.stack.push(ParserToken::PartialList(factory))
+                        children.push(spans);
+                        self.stack
+                            .push(ParserToken::PartialList(factory, start, children))
                     }
                     Some(ParserToken::Dot) => {
                         self.stack.push(ParserToken::Dot);
 
-                        self.stack.push(ParserToken::Datum(datum));
+                        self.stack.push(ParserToken::Datum(datum, spans));
 
                         if self.push_input()? {
                             return Err(ParserError::TokenizerError(
@@ -140,48 +255,116 @@ impl<'a> Parser<'a> {
                             ));
                         }
                     }
-                    Some(ParserToken::Mark(mark)) => {
-                        let name = AstSymbol::new(match mark {
-                            Mark::Quote => "quote",
-                        });
+                    Some(ParserToken::Mark(Mark::Quote, mark_span)) => {
+                        self.depth -= 1;
+                        let ret_list = vec![AstSymbol::new("quote").into(), datum];
+                        let ret_spans = SourceSpans {
+                            span: Span {
+                                start: mark_span.start,
+                                end: spans.span.end,
+                            },
+                            children: vec![SourceSpans::leaf(mark_span), spans],
+                        };
+
+                        self.stack
+                            .push(ParserToken::Datum(ret_list.into(), ret_spans));
+                    }
+                    Some(ParserToken::Mark(Mark::Vector, mark_span)) => {
+                        let elements = datum.into_proper_list().map_err(|_| ParserError::Syntax)?;
+
+                        self.stack.push(ParserToken::Datum(
+                            AstNode::from_vector(elements),
+                            SourceSpans {
+                                span: Span {
+                                    start: mark_span.start,
+                                    end: spans.span.end,
+                                },
+                                children: spans.children,
+                            },
+                        ));
+                    }
+                    Some(ParserToken::Mark(Mark::Bytevector, mark_span)) => {
+                        let elements = datum.into_proper_list().map_err(|_| ParserError::Syntax)?;
 
-                        let ret_list = vec![name.into(), datum];
+                        let bytes = elements
+                            .iter()
+                            .map(|element| {
+                                element
+                                    .as_number()
+                                    .filter(|num| (0..=255).contains(num))
+                                    .map(|num| num as u8)
+                                    .ok_or(ParserError::ByteOutOfRange)
+                            })
+                            .collect::<Result<_, _>>()?;
 
-                        self.stack.push(ParserToken::Datum(ret_list.into()));
+                        self.stack.push(ParserToken::Datum(
+                            AstNode::from_bytevector(bytes),
+                            SourceSpans {
+                                span: Span {
+                                    start: mark_span.start,
+                                    end: spans.span.end,
+                                },
+                                children: spans.children,
+                            },
+                        ));
                     }
+                    //The commented out datum is complete so drop it.
+                    Some(ParserToken::Mark(Mark::DatumComment, _)) => self.depth -= 1,
                     _ => return Err(ParserError::Syntax),
                 },
-                Some(ParserToken::ListEnd) => match self.stack.pop() {
-                    Some(ParserToken::PartialList(factory)) => {
+                Some(ParserToken::ListEnd(end)) => match self.stack.pop() {
+                    Some(ParserToken::PartialList(factory, start, children)) => {
+                        self.depth -= 1;
                         let datum = factory.build();
-                        self.stack.push(ParserToken::Datum(datum.into()));
+                        let spans = SourceSpans {
+                            span: Span { start, end },
+                            children,
+                        };
+                        self.stack.push(ParserToken::Datum(datum.into(), spans));
                     }
-                    Some(ParserToken::Datum(rest)) => {
+                    Some(ParserToken::Datum(rest, rest_spans)) => {
                         if let Some(ParserToken::Dot) = self.stack.pop() {
                         } else {
                             return Err(ParserError::Syntax);
                         }
 
-                        let factory = if let Some(ParserToken::PartialList(fac)) = self.stack.pop()
-                        {
-                            fac
-                        } else {
-                            return Err(ParserError::Syntax);
-                        };
+                        let (factory, start, mut children) =
+                            if let Some(ParserToken::PartialList(fac, start, children)) =
+                                self.stack.pop()
+                            {
+                                (fac, start, children)
+                            } else {
+                                return Err(ParserError::Syntax);
+                            };
+                        self.depth -= 1;
 
                         let list_or_err = factory.build_with_tail(rest);
 
                         if let Some(list) = list_or_err {
-                            self.stack.push(ParserToken::Datum(list.into()))
+                            children.push(rest_spans);
+                            let spans = SourceSpans {
+                                span: Span { start, end },
+                                children,
+                            };
+                            self.stack.push(ParserToken::Datum(list.into(), spans))
                         } else {
                             return Err(ParserError::Syntax);
                         }
                     }
+                    None => {
+                        //end is just past the ), which is always one column wide.
+                        return Err(ParserError::UnexpectedListEnd(Position {
+                            line: end.line,
+                            column: end.column - 1,
+                        }));
+                    }
                     _ => return Err(ParserError::Syntax),
                 },
                 Some(ParserToken::Dot) => {
-                    if let Some(ParserToken::PartialList(list)) = self.stack.pop() {
-                        self.stack.push(ParserToken::PartialList(list))
+                    if let Some(ParserToken::PartialList(list, start, children)) = self.stack.pop()
+                    {
+                        self.stack
+                            .push(ParserToken::PartialList(list, start, children))
                     } else {
                         return Err(ParserError::Syntax);
                     }
@@ -205,12 +388,28 @@ impl<'a> Parser<'a> {
             }
         }
     }
+
+    //Reads exactly one datum and returns it with the input after it, which is left unread.
+    //An UnexpectedEndOfFile error means the datum continues past the end of the input, so a
+    //REPL can append the next line to the input left by the last datum and parse that again.
+    pub fn next_datum(&mut self) -> Result<Option<(AstNode, &'a str)>, ParserError> {
+        Ok(self
+            .iter_once()?
+            .map(|(datum, _)| (datum, self.tokenizer.rest())))
+    }
+
+    //Like next but also returns where the datum and everything in it came from.
+    pub fn next_with_spans(&mut self) -> Option<Result<(AstNode, SourceSpans), ParserError>> {
+        self.iter_once().transpose()
+    }
 }
 
 impl<'a> Iterator for Parser<'a> {
     type Item = Result<AstNode, ParserError>;
 
     fn next(&mut self) -> Option<Result<AstNode, ParserError>> {
-        self.iter_once().transpose()
+        self.next_datum()
+            .transpose()
+            .map(|result| result.map(|(datum, _)| datum))
     }
 }
@@ -0,0 +1,512 @@
+use crate::ast::{AstListBuilder, AstNode, AstSymbol};
+
+use super::tokenizer::{
+    read_hash_token, Block, Mark, Token, Tokenizer, TokenizerError, DEFAULT_MAX_TOKEN_LEN,
+};
+use super::{Parser, ParserError, Position, SourceSpans, Span, DEFAULT_MAX_DEPTH};
+
+fn parse_one(input: &str) -> Result<AstNode, ParserError> {
+    Parser::new(input).next().unwrap()
+}
+
+#[test]
+fn token_too_long() {
+    let long_string = format!("\"{}\"", "a".repeat(100));
+
+    assert!(Parser::with_max_token_len(&long_string, 100)
+        .next()
+        .unwrap()
+        .is_ok());
+    assert!(matches!(
+        Parser::with_max_token_len(&long_string, 99).next(),
+        Some(Err(ParserError::TokenizerError(
+            TokenizerError::TokenTooLong
+        )))
+    ));
+
+    let long_symbol = "a".repeat(DEFAULT_MAX_TOKEN_LEN + 1);
+    assert!(matches!(
+        parse_one(&long_symbol),
+        Err(ParserError::TokenizerError(TokenizerError::TokenTooLong))
+    ));
+    assert_eq!(
+        parse_one(&long_symbol[1..]).unwrap(),
+        AstSymbol::new(&long_symbol[1..]).into()
+    );
+}
+
+#[test]
+fn max_depth() {
+    let nested = |depth| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+
+    assert!(
+        Parser::with_limits(&nested(100), DEFAULT_MAX_TOKEN_LEN, 100)
+            .next()
+            .unwrap()
+            .is_ok()
+    );
+    assert!(matches!(
+        Parser::with_limits(&nested(101), DEFAULT_MAX_TOKEN_LEN, 100).next(),
+        Some(Err(ParserError::TooDeep))
+    ));
+    assert!(
+        Parser::with_limits("(() ((1)) (1 . (2)) ((2 . 3)))", DEFAULT_MAX_TOKEN_LEN, 3)
+            .next()
+            .unwrap()
+            .is_ok()
+    );
+
+    assert!(matches!(
+        parse_one(&"(".repeat(1_000_000)),
+        Err(ParserError::TooDeep)
+    ));
+
+    //Quotes and datum comments nest too.
+    let quoted = |depth| format!("{}a", "'".repeat(depth));
+    assert!(
+        Parser::with_limits(&quoted(100), DEFAULT_MAX_TOKEN_LEN, 100)
+            .next()
+            .unwrap()
+            .is_ok()
+    );
+    assert!(matches!(
+        Parser::with_limits(&quoted(101), DEFAULT_MAX_TOKEN_LEN, 100).next(),
+        Some(Err(ParserError::TooDeep))
+    ));
+    assert!(matches!(
+        parse_one(&quoted(200_000)),
+        Err(ParserError::TooDeep)
+    ));
+    assert!(matches!(
+        parse_one(&format!("{}a b", "#;".repeat(200_000))),
+        Err(ParserError::TooDeep)
+    ));
+    assert!(
+        Parser::with_limits("('(1) #;2 ('3))", DEFAULT_MAX_TOKEN_LEN, 3)
+            .next()
+            .unwrap()
+            .is_ok()
+    );
+    assert!(parse_one(&nested(DEFAULT_MAX_DEPTH)).is_ok());
+}
+
+fn parse_all(input: &str) -> Vec<AstNode> {
+    Parser::new(input).collect::<Result<_, _>>().unwrap()
+}
+
+fn quote(datum: AstNode) -> AstNode {
+    vec![AstSymbol::new("quote").into(), datum].into()
+}
+
+#[test]
+fn datum_comment() {
+    assert_eq!(parse_all("#;(1 2) 3 #; 4"), vec![AstNode::from_number(3)]);
+    assert_eq!(
+        parse_one("(1 #;2 3 #;(4))").unwrap(),
+        vec![AstNode::from_number(1), AstNode::from_number(3)].into()
+    );
+    assert!(parse_one("(1 #;)").is_err());
+    assert!(parse_one("#;").is_err());
+}
+
+#[test]
+fn datum_comment_quote() {
+    assert_eq!(
+        parse_one("'#;a b").unwrap(),
+        quote(AstSymbol::new("b").into())
+    );
+    assert_eq!(parse_all("#;'a b"), vec![AstSymbol::new("b").into()]);
+}
+
+#[test]
+fn datum_comment_dotted() {
+    let mut expected = AstListBuilder::new();
+    expected.push(AstNode::from_number(1));
+    let expected = expected.build_with_tail(AstNode::from_number(3)).unwrap();
+
+    assert_eq!(parse_one("(1 . #;2 3)").unwrap(), expected.clone().into());
+    assert_eq!(parse_one("(1 . 3 #;2)").unwrap(), expected.into());
+    assert!(parse_one("(1 . #;2)").is_err());
+}
+
+#[test]
+fn datum_comment_block_comment() {
+    let y: AstNode = AstSymbol::new("y").into();
+    assert_eq!(parse_all("#; #|c|# x y"), vec![y.clone()]);
+    assert_eq!(parse_all("#;#|c|#x y"), vec![y.clone()]);
+    assert_eq!(parse_all("#;(a #| ) |# (b #|c|#)) y"), vec![y.clone()]);
+    assert_eq!(parse_all("#;(a #| #| ( |# |#) y"), vec![y]);
+    assert!(parse_one("#; #|c|#").is_err());
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token<'_>>, TokenizerError> {
+    Tokenizer::new(input, DEFAULT_MAX_TOKEN_LEN, false).collect()
+}
+
+#[test]
+fn hash_booleans() {
+    assert_eq!(
+        tokenize("#t #f #true #false").unwrap(),
+        vec![
+            Token::Bool(true),
+            Token::Bool(false),
+            Token::Bool(true),
+            Token::Bool(false)
+        ]
+    );
+    assert_eq!(
+        tokenize("(#t)").unwrap(),
+        vec![
+            Token::Block(Block::Start),
+            Token::Bool(true),
+            Token::Block(Block::End)
+        ]
+    );
+    assert!(matches!(
+        tokenize("#tru"),
+        Err(TokenizerError::UnknownToken)
+    ));
+}
+
+#[test]
+fn hash_chars() {
+    assert_eq!(
+        tokenize("#\\a #\\( #\\  #\\space #\\newline #\\x41 #\\λ").unwrap(),
+        vec![
+            Token::Char('a'),
+            Token::Char('('),
+            Token::Char(' '),
+            Token::Char(' '),
+            Token::Char('\n'),
+            Token::Char('A'),
+            Token::Char('λ')
+        ]
+    );
+    assert_eq!(
+        tokenize("#\\a)").unwrap(),
+        vec![Token::Char('a'), Token::Block(Block::End)]
+    );
+    assert!(matches!(
+        tokenize("#\\bogus"),
+        Err(TokenizerError::UnknownToken)
+    ));
+    assert!(matches!(
+        tokenize("#\\"),
+        Err(TokenizerError::UnexpectedEndOfFile)
+    ));
+}
+
+#[test]
+fn hash_comments() {
+    assert_eq!(
+        tokenize("#;1").unwrap(),
+        vec![Token::Mark(Mark::DatumComment), Token::Number("1")]
+    );
+    assert_eq!(
+        tokenize("#| a #| nested |# b |# 1").unwrap(),
+        vec![Token::Number("1")]
+    );
+    assert!(matches!(
+        tokenize("#| a #| b |#"),
+        Err(TokenizerError::UnexpectedEndOfFile)
+    ));
+}
+
+#[test]
+fn hash_unknown_or_clipped() {
+    assert!(matches!(
+        tokenize("#"),
+        Err(TokenizerError::UnexpectedEndOfFile)
+    ));
+    assert!(matches!(
+        tokenize("1 #"),
+        Err(TokenizerError::UnexpectedEndOfFile)
+    ));
+    assert!(matches!(tokenize("#q"), Err(TokenizerError::UnknownToken)));
+    assert!(matches!(tokenize("# t"), Err(TokenizerError::UnknownToken)));
+}
+
+#[test]
+fn hash_dispatch() {
+    let cases = vec![
+        ("#t x", Token::Bool(true), " x"),
+        ("#false)", Token::Bool(false), ")"),
+        ("#\\a b", Token::Char('a'), " b"),
+        ("#(1)", Token::Mark(Mark::Vector), "(1)"),
+        ("#u8(1)", Token::Mark(Mark::Bytevector), "(1)"),
+        ("#;1", Token::Mark(Mark::DatumComment), "1"),
+        ("#|a|# 1", Token::Comment("#|a|#"), " 1"),
+    ];
+    for (input, token, rest) in cases {
+        assert_eq!(read_hash_token(input).unwrap(), (token, rest));
+    }
+
+    assert!(matches!(
+        read_hash_token("#q"),
+        Err(TokenizerError::UnknownToken)
+    ));
+    assert!(matches!(
+        read_hash_token("#u7("),
+        Err(TokenizerError::UnknownToken)
+    ));
+    assert!(matches!(
+        read_hash_token("#"),
+        Err(TokenizerError::UnexpectedEndOfFile)
+    ));
+}
+
+#[test]
+fn string_line_continuation() {
+    assert_eq!(
+        parse_one("\"abc\\   \n   def\"").unwrap(),
+        AstNode::from_string("abcdef".to_string())
+    );
+    assert_eq!(
+        parse_one("\"abc\\\r\n\tdef\"").unwrap(),
+        AstNode::from_string("abcdef".to_string())
+    );
+    assert_eq!(
+        parse_one("\"two\nlines\"").unwrap(),
+        AstNode::from_string("two\nlines".to_string())
+    );
+    assert!(matches!(
+        parse_one("\"abc\\  def\""),
+        Err(ParserError::UnknownEscapeSequence)
+    ));
+}
+
+#[test]
+fn vector_literal() {
+    assert_eq!(
+        parse_one("#(1 (2) #(3))").unwrap(),
+        AstNode::from_vector(vec![
+            AstNode::from_number(1),
+            vec![AstNode::from_number(2)].into(),
+            AstNode::from_vector(vec![AstNode::from_number(3)])
+        ])
+    );
+    assert_eq!(parse_one("#()").unwrap(), AstNode::from_vector(Vec::new()));
+    assert!(parse_one("#(1 . 2)").is_err());
+    assert!(parse_one("# (1)").is_err());
+}
+
+#[test]
+fn bytevector_literal() {
+    assert_eq!(
+        parse_one("#u8(1 2 255)").unwrap(),
+        AstNode::from_bytevector(vec![1, 2, 255])
+    );
+    assert_eq!(
+        parse_one("#u8()").unwrap(),
+        AstNode::from_bytevector(Vec::new())
+    );
+    assert!(matches!(
+        parse_one("#u8(1 256)"),
+        Err(ParserError::ByteOutOfRange)
+    ));
+    assert!(matches!(
+        parse_one("#u8(-1)"),
+        Err(ParserError::ByteOutOfRange)
+    ));
+    assert!(matches!(
+        parse_one("#u8(a)"),
+        Err(ParserError::ByteOutOfRange)
+    ));
+    assert!(parse_one("#u8(1 . 2)").is_err());
+    assert!(parse_one("#u9(1)").is_err());
+}
+
+#[test]
+fn source_spans() {
+    let span = |start: (usize, usize), end: (usize, usize)| Span {
+        start: Position {
+            line: start.0,
+            column: start.1,
+        },
+        end: Position {
+            line: end.0,
+            column: end.1,
+        },
+    };
+
+    let program = "; leading comment
+(define (f x)
+  (if (zero? x)
+      'done
+      (f (- x 1))))
+#(1 \"λ\" (2 . 3))";
+    let mut parser = Parser::new(program);
+
+    let (_, define_spans) = parser.next_with_spans().unwrap().unwrap();
+    assert_eq!(define_spans.span, span((2, 1), (5, 20)));
+
+    let if_spans = &define_spans.children[2];
+    assert_eq!(if_spans.span, span((3, 3), (5, 19)));
+    assert_eq!(if_spans.children[1].span, span((3, 7), (3, 16)));
+    assert_eq!(
+        if_spans.children[1].children[1].span,
+        span((3, 14), (3, 15))
+    );
+
+    let quote_spans = &if_spans.children[2];
+    assert_eq!(quote_spans.span, span((4, 7), (4, 12)));
+    assert_eq!(quote_spans.children[1].span, span((4, 8), (4, 12)));
+
+    let (_, vector_spans) = parser.next_with_spans().unwrap().unwrap();
+    assert_eq!(vector_spans.span, span((6, 1), (6, 17)));
+    assert_eq!(vector_spans.children[1].span, span((6, 5), (6, 8)));
+    assert_eq!(
+        vector_spans.children[2],
+        SourceSpans {
+            span: span((6, 9), (6, 16)),
+            children: vec![
+                SourceSpans {
+                    span: span((6, 10), (6, 11)),
+                    children: Vec::new()
+                },
+                SourceSpans {
+                    span: span((6, 14), (6, 15)),
+                    children: Vec::new()
+                },
+            ],
+        }
+    );
+
+    assert!(parser.next_with_spans().is_none());
+}
+
+#[test]
+fn trailing_whitespace_and_comments() {
+    assert_eq!(
+        parse_all("42 ; trailing comment\n  "),
+        vec![AstNode::from_number(42)]
+    );
+    assert_eq!(parse_all("42 ; no newline"), vec![AstNode::from_number(42)]);
+    assert_eq!(
+        parse_all("42\r\n\t#| block |# ; line\n"),
+        vec![AstNode::from_number(42)]
+    );
+    assert_eq!(
+        parse_all("(a) #;(b)  "),
+        vec![vec![AstSymbol::new("a").into()].into()]
+    );
+    assert!(parse_all(" ; only a comment\n").is_empty());
+}
+
+#[test]
+fn comment_tokens() {
+    let input = "; header\n(a ; after a\n #| block\n |#b)#|x|#;end";
+    let tokens = Tokenizer::new(input, DEFAULT_MAX_TOKEN_LEN, true)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(
+        tokens,
+        vec![
+            Token::Comment("; header"),
+            Token::Block(Block::Start),
+            Token::Symbol("a"),
+            Token::Comment("; after a"),
+            Token::Comment("#| block\n |#"),
+            Token::Symbol("b"),
+            Token::Block(Block::End),
+            Token::Comment("#|x|#"),
+            Token::Comment(";end"),
+        ]
+    );
+
+    //Without keeping comments the same input has no comment tokens.
+    assert_eq!(tokenize(input).unwrap().len(), 4);
+}
+
+#[test]
+fn mixed_line_endings() {
+    let input = "a\r\nb\rc\n\x0Cd\x0B;note\r\n(e)";
+    assert_eq!(
+        tokenize(input).unwrap(),
+        vec![
+            Token::Symbol("a"),
+            Token::Symbol("b"),
+            Token::Symbol("c"),
+            Token::Symbol("d"),
+            Token::Block(Block::Start),
+            Token::Symbol("e"),
+            Token::Block(Block::End),
+        ]
+    );
+
+    let mut tokenizer = Tokenizer::new(input, DEFAULT_MAX_TOKEN_LEN, true);
+    let mut lines = Vec::new();
+    while let Some(token) = tokenizer.next() {
+        if let Token::Comment(comment) = token.unwrap() {
+            assert_eq!(comment, ";note");
+        }
+        lines.push(tokenizer.token_span().start.line);
+    }
+    assert_eq!(lines, vec![1, 2, 3, 4, 4, 5, 5, 5]);
+
+    let mut parser = Parser::new("(a\r\n\r\n b)\r(c)");
+    let (_, spans) = parser.next_with_spans().unwrap().unwrap();
+    assert_eq!(spans.span.end, Position { line: 3, column: 4 });
+    let (_, spans) = parser.next_with_spans().unwrap().unwrap();
+    assert_eq!(spans.span.start, Position { line: 4, column: 1 });
+}
+
+#[test]
+fn unbalanced_list_end() {
+    assert!(matches!(
+        parse_one(")"),
+        Err(ParserError::UnexpectedListEnd(Position {
+            line: 1,
+            column: 1
+        }))
+    ));
+
+    let mut parser = Parser::new("(+ 1 2)\n  ) 3");
+    assert!(parser.next().unwrap().is_ok());
+    assert!(matches!(
+        parser.next(),
+        Some(Err(ParserError::UnexpectedListEnd(Position {
+            line: 2,
+            column: 3
+        })))
+    ));
+
+    let err = Parser::new("(a))").nth(1).unwrap().unwrap_err();
+    assert_eq!(err.to_string(), "Unexpected ) at 1:4.");
+    assert_eq!(parse_all("(a (b)) (c)").len(), 2);
+    assert!(matches!(parse_one("(a . )"), Err(ParserError::Syntax)));
+}
+
+#[test]
+fn next_datum() {
+    let mut parser = Parser::new("a (b c) ; done\n");
+    let (datum, rest) = parser.next_datum().unwrap().unwrap();
+    assert_eq!(datum, AstSymbol::new("a").into());
+    assert_eq!(rest, " (b c) ; done\n");
+
+    let (datum, rest) = parser.next_datum().unwrap().unwrap();
+    let expected: AstNode = vec![AstSymbol::new("b").into(), AstSymbol::new("c").into()].into();
+    assert_eq!(datum, expected);
+    assert_eq!(rest, " ; done\n");
+    assert!(parser.next_datum().unwrap().is_none());
+
+    //A datum split over two lines parses once the second line is appended to what was left.
+    let mut input = String::from("1 (2\n");
+    let mut parser = Parser::new(&input);
+    let rest = parser.next_datum().unwrap().unwrap().1.to_string();
+    assert!(matches!(
+        parser.next_datum(),
+        Err(ParserError::TokenizerError(
+            TokenizerError::UnexpectedEndOfFile
+        ))
+    ));
+
+    input = rest + "3)";
+    let mut parser = Parser::new(&input);
+    let (datum, rest) = parser.next_datum().unwrap().unwrap();
+    assert_eq!(
+        datum,
+        vec![AstNode::from_number(2), AstNode::from_number(3)].into()
+    );
+    assert_eq!(rest, "");
+}
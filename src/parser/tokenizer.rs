@@ -17,10 +17,54 @@
     along with scheme-oxide.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::fmt;
+
 use regex::Regex;
 
 use lazy_static::lazy_static;
 
+//Lines and columns both start at 1. Columns count characters rather than bytes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    //\n, \r and \r\n each end a line.
+    fn advance(&mut self, text: &str) {
+        let mut after_return = false;
+        for c in text.chars() {
+            match c {
+                '\n' if after_return => (),
+                '\n' | '\r' => {
+                    self.line += 1;
+                    self.column = 1;
+                }
+                _ => self.column += 1,
+            }
+            after_return = c == '\r';
+        }
+    }
+}
+
+//The end is just past the last character.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}-{}:{}",
+            self.start.line, self.start.column, self.end.line, self.end.column
+        )
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub enum Block {
     Start,
@@ -30,6 +74,9 @@ pub enum Block {
 #[derive(Debug, Eq, PartialEq)]
 pub enum Mark {
     Quote,
+    DatumComment,
+    Vector,
+    Bytevector,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -39,12 +86,15 @@ pub enum Token<'a> {
     Symbol(&'a str),
     Number(&'a str),
     Bool(bool),
+    Char(char),
     Dot,
     Mark(Mark),
+    //Only produced when the tokenizer keeps comments. Includes the ; or #| and |#.
+    Comment(&'a str),
 }
 
 fn gen_regex() -> Regex {
-    let comment = "(?:;.*)";
+    let comment = r"(?:;[^\r\n]*)";
     let whitespace = format!("(?:[[:space:]]|{})", comment);
 
     let delmer = format!(r#"(?:{}|[()";]|$)"#, whitespace);
@@ -58,7 +108,7 @@ fn gen_regex() -> Regex {
     let odd_symbol = r"(?:[+-]|\.{3})";
     let symbol = format!("(?:(?P<symbol>{}|{}){})", normal_symbol, odd_symbol, delmer);
 
-    let string_body = |id| format!(r#"(?P<{}Body>(?:[^"\\\n]|\\.)*)"#, id);
+    let string_body = |id| format!(r#"(?P<{}Body>(?:[^"\\]|\\(?s:.))*)"#, id);
     let good_string = format!(r#"(?:"{}")"#, string_body("goodString"));
     let bad_eof_string = format!(r#"(?:"{}\\?$)"#, string_body("badEofString"));
 
@@ -66,18 +116,17 @@ fn gen_regex() -> Regex {
 
     let block = r"(?P<block>\(|\))";
 
-    let boolean = format!("(?:(?P<boolean>#t|#f){})", delmer);
-
     let dot = format!(r"(?:(?P<dot>\.){})", delmer);
 
     let mark = "(?P<mark>')";
 
     //Matches any multi character sequence cut off by end of buffer
-    let clipped = r"(?P<clipped>(?:\.{2}|#)$)";
+    //Tokens starting with # are handled by gen_hash_token instead.
+    let clipped = r"(?P<clipped>\.{2}$)";
 
     let regex_str = format!(
-        "^(?:{}|{}|{}|{}|(?P<whitespace>{}+)|{}|{}|{}|{}|{})",
-        number, symbol, good_string, block, whitespace, bad_eof_string, clipped, boolean, dot, mark
+        "^(?:{}|{}|{}|{}|(?P<whitespace>{}+)|{}|{}|{}|{})",
+        number, symbol, good_string, block, whitespace, bad_eof_string, clipped, dot, mark
     );
 
     Regex::new(&regex_str).unwrap()
@@ -87,6 +136,139 @@ lazy_static! {
     static ref REGEX: Regex = gen_regex();
 }
 
+//The characters matched by [[:space:]] in the regex.
+fn is_space(c: char) -> bool {
+    c.is_ascii_whitespace() || c == '\x0B'
+}
+
+fn is_line_end(c: char) -> bool {
+    c == '\n' || c == '\r'
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || c == '(' || c == ')' || c == '"' || c == ';'
+}
+
+//Splits input before the first delimiter.
+fn split_at_delimiter(input: &str) -> (&str, &str) {
+    input.split_at(input.find(is_delimiter).unwrap_or(input.len()))
+}
+
+fn char_from_name(name: &str) -> Option<char> {
+    Some(match name {
+        "alarm" => '\x07',
+        "backspace" => '\x08',
+        "delete" => '\x7f',
+        "escape" => '\x1b',
+        "newline" => '\n',
+        "null" | "nul" => '\0',
+        "return" => '\r',
+        "space" => ' ',
+        "tab" => '\t',
+        _ => {
+            let hex = name.strip_prefix('x')?;
+            return u32::from_str_radix(hex, 16)
+                .ok()
+                .and_then(std::char::from_u32);
+        }
+    })
+}
+
+//Each reads the token at the start of input, which begins with #, and returns it with the
+//remaining input. Block comments always come out as Token::Comment.
+type HashHandler = for<'a> fn(&'a str) -> Result<(Token<'a>, &'a str), TokenizerError>;
+
+//Maps the character after a # to the handler for that kind of token.
+const HASH_DISPATCH: &[(char, HashHandler)] = &[
+    ('t', read_boolean),
+    ('f', read_boolean),
+    ('\\', read_char),
+    ('(', read_vector_start),
+    ('u', read_bytevector_start),
+    (';', read_datum_comment),
+    ('|', read_block_comment),
+];
+
+pub(super) fn read_hash_token(input: &str) -> Result<(Token<'_>, &str), TokenizerError> {
+    let dispatch = input[1..]
+        .chars()
+        .next()
+        .ok_or(TokenizerError::UnexpectedEndOfFile)?;
+
+    let handler = HASH_DISPATCH
+        .iter()
+        .find(|(c, _)| *c == dispatch)
+        .map(|(_, handler)| handler)
+        .ok_or(TokenizerError::UnknownToken)?;
+
+    handler(input)
+}
+
+fn read_boolean(input: &str) -> Result<(Token<'_>, &str), TokenizerError> {
+    let (word, rest) = split_at_delimiter(&input[1..]);
+    let boolean = match word {
+        "t" | "true" => true,
+        "f" | "false" => false,
+        _ => return Err(TokenizerError::UnknownToken),
+    };
+    Ok((Token::Bool(boolean), rest))
+}
+
+fn read_char(input: &str) -> Result<(Token<'_>, &str), TokenizerError> {
+    let after_slash = &input[2..];
+    let first_char = after_slash
+        .chars()
+        .next()
+        .ok_or(TokenizerError::UnexpectedEndOfFile)?;
+
+    //The first character is always part of the token even if it is a delimiter.
+    let (name_rest, rest) = split_at_delimiter(&after_slash[first_char.len_utf8()..]);
+    let c = if name_rest.is_empty() {
+        first_char
+    } else {
+        let name = &after_slash[..first_char.len_utf8() + name_rest.len()];
+        char_from_name(name).ok_or(TokenizerError::UnknownToken)?
+    };
+    Ok((Token::Char(c), rest))
+}
+
+//The ( is left for the parser to read as the start of the vector's elements.
+fn read_vector_start(input: &str) -> Result<(Token<'_>, &str), TokenizerError> {
+    Ok((Token::Mark(Mark::Vector), &input[1..]))
+}
+
+fn read_bytevector_start(input: &str) -> Result<(Token<'_>, &str), TokenizerError> {
+    if input.starts_with("#u8(") {
+        Ok((Token::Mark(Mark::Bytevector), &input[3..]))
+    } else {
+        Err(TokenizerError::UnknownToken)
+    }
+}
+
+fn read_datum_comment(input: &str) -> Result<(Token<'_>, &str), TokenizerError> {
+    Ok((Token::Mark(Mark::DatumComment), &input[2..]))
+}
+
+fn read_block_comment(input: &str) -> Result<(Token<'_>, &str), TokenizerError> {
+    let mut depth = 1;
+    let mut rest = &input[2..];
+    while depth != 0 {
+        if rest.starts_with("|#") {
+            depth -= 1;
+            rest = &rest[2..];
+        } else if rest.starts_with("#|") {
+            depth += 1;
+            rest = &rest[2..];
+        } else if let Some(c) = rest.chars().next() {
+            rest = &rest[c.len_utf8()..];
+        } else {
+            return Err(TokenizerError::UnexpectedEndOfFile);
+        }
+    }
+
+    Ok((Token::Comment(&input[..input.len() - rest.len()]), rest))
+}
+
 //Type used to store more information about each token than is exposed to parser
 enum InternalToken<'a> {
     PublicToken(Token<'a>),
@@ -119,15 +301,72 @@ impl<'a> InternalToken<'a> {
 pub enum TokenizerError {
     UnexpectedEndOfFile,
     UnknownToken,
+    TokenTooLong,
 }
 
+//Longest symbol, number, or string body in bytes accepted by default.
+//Bounds the memory a single token from untrusted input can take up.
+pub const DEFAULT_MAX_TOKEN_LEN: usize = 1 << 20;
+
 pub struct Tokenizer<'a> {
     input: &'a str,
+    max_token_len: usize,
+    position: Position,
+    token_start: Position,
+    keep_comments: bool,
 }
 
 impl<'a> Tokenizer<'a> {
-    pub fn new(input: &'a str) -> Self {
-        Tokenizer { input }
+    //With keep_comments, line and block comments come out as Token::Comment instead of being
+    //skipped like whitespace.
+    pub fn new(input: &'a str, max_token_len: usize, keep_comments: bool) -> Self {
+        let start = Position { line: 1, column: 1 };
+        Tokenizer {
+            input,
+            max_token_len,
+            position: start,
+            token_start: start,
+            keep_comments,
+        }
+    }
+
+    //Span of the token last returned by next.
+    pub fn token_span(&self) -> Span {
+        Span {
+            start: self.token_start,
+            end: self.position,
+        }
+    }
+
+    //Input after the token last returned by next.
+    pub fn rest(&self) -> &'a str {
+        self.input
+    }
+
+    fn advance_to(&mut self, rest: &'a str) {
+        self.position
+            .advance(&self.input[..self.input.len() - rest.len()]);
+        self.input = rest;
+    }
+
+    fn check_len(&self, token: &str) -> Result<(), TokenizerError> {
+        if token.len() > self.max_token_len {
+            Err(TokenizerError::TokenTooLong)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn gen_hash_token(&mut self) -> Result<InternalToken<'a>, TokenizerError> {
+        let (token, rest) = read_hash_token(self.input)?;
+        let token = match token {
+            Token::Comment(_) if !self.keep_comments => InternalToken::Whitespace,
+            token => InternalToken::PublicToken(token),
+        };
+
+        self.advance_to(rest);
+
+        Ok(token)
     }
 
     fn gen_token(&mut self) -> Result<InternalToken<'a>, TokenizerError> {
@@ -135,6 +374,27 @@ impl<'a> Tokenizer<'a> {
             return Ok(InternalToken::EndOfFile);
         }
 
+        if self.input.starts_with('#') {
+            return self.gen_hash_token();
+        }
+
+        //The regex treats line comments as whitespace, so split them off first.
+        if self.keep_comments {
+            if self.input.starts_with(';') {
+                let (comment, rest) = self
+                    .input
+                    .split_at(self.input.find(is_line_end).unwrap_or(self.input.len()));
+                self.advance_to(rest);
+                return Ok(InternalToken::PublicToken(Token::Comment(comment)));
+            }
+
+            let rest = self.input.trim_start_matches(is_space);
+            if rest.len() != self.input.len() {
+                self.advance_to(rest);
+                return Ok(InternalToken::Whitespace);
+            }
+        }
+
         let unchecked_captures = REGEX.captures(&self.input);
         let captures = if let Some(cap) = unchecked_captures {
             cap
@@ -151,6 +411,7 @@ impl<'a> Tokenizer<'a> {
             return Err(TokenizerError::UnexpectedEndOfFile);
         } else {
             InternalToken::PublicToken(if let Some(string) = captures.name("goodStringBody") {
+                self.check_len(string.as_str())?;
                 Token::TString(string.as_str())
             } else if let Some(block) = captures.name("block") {
                 let block_char = block.as_str();
@@ -161,20 +422,12 @@ impl<'a> Tokenizer<'a> {
                 } else {
                     unreachable!()
                 }
-            } else if let Some(boolean) = captures.name("boolean") {
-                end_of_token = boolean.end();
-                let bool_str = boolean.as_str();
-                if bool_str == "#t" {
-                    Token::Bool(true)
-                } else if bool_str == "#f" {
-                    Token::Bool(false)
-                } else {
-                    unreachable!()
-                }
             } else if let Some(symbol) = captures.name("symbol") {
+                self.check_len(symbol.as_str())?;
                 end_of_token = symbol.end();
                 Token::Symbol(symbol.as_str())
             } else if let Some(number) = captures.name("number") {
+                self.check_len(number.as_str())?;
                 end_of_token = number.end();
                 Token::Number(number.as_str())
             } else if let Some(dot) = captures.name("dot") {
@@ -191,7 +444,7 @@ impl<'a> Tokenizer<'a> {
             })
         };
 
-        self.input = &self.input[end_of_token..];
+        self.advance_to(&self.input[end_of_token..]);
 
         Ok(ret)
     }
@@ -203,6 +456,7 @@ impl<'a> Iterator for Tokenizer<'a> {
     fn next(&mut self) -> Option<Self::Item> {
         let mut unchecked_token;
         loop {
+            self.token_start = self.position;
             unchecked_token = self.gen_token();
             if let Ok(ref token) = unchecked_token {
                 //Grab another token if its whitespace
@@ -17,8 +17,12 @@
     along with scheme-oxide.  If not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::env;
+use std::fs;
+
 use crate::environment;
-use crate::interpreter::eval;
+use crate::interpreter::{eval, eval_without_prelude, with_include_dir, RuntimeError};
+use crate::types::SchemeType;
 
 #[test]
 fn add_zero() {
@@ -70,3 +74,1765 @@ fn list_fun() {
         environment::s_true().into()
     );
 }
+
+#[test]
+fn internal_define() {
+    assert_eq!(
+        eval("(let () (define a 1) (define b (+ a 1)) b)").unwrap(),
+        SchemeType::Number(2)
+    );
+    //b is bound but not yet initialized when a's init reads it.
+    assert_eq!(
+        eval("(let () (define a b) (define b 1) a)")
+            .unwrap_err()
+            .to_string(),
+        "b: Tried to read from undef."
+    );
+}
+
+#[test]
+fn internal_define_mutual_recursion() {
+    let prog = |n| {
+        format!(
+            "(let ()
+                (define (is-even? n) (if (= n 0) #t (is-odd? (- n 1))))
+                (define (is-odd? n) (if (= n 0) #f (is-even? (- n 1))))
+                (is-even? {}))",
+            n
+        )
+    };
+
+    assert_eq!(eval(&prog(100)).unwrap(), environment::s_true());
+    assert_eq!(eval(&prog(7)).unwrap(), environment::s_false());
+    //Neither name leaks out of the body.
+    assert_eq!(
+        eval(&format!("{} (is-odd? 1)", prog(0)))
+            .unwrap_err()
+            .to_string(),
+        "is-odd? is not defined."
+    );
+}
+
+#[test]
+fn apply_fun() {
+    assert_eq!(
+        eval("(apply + 1 2 (list 3 4))").unwrap(),
+        SchemeType::Number(10)
+    );
+    assert_eq!(eval("(apply + '())").unwrap(), SchemeType::Number(0));
+    assert!(eval("(apply + 1)").is_err());
+}
+
+#[test]
+fn memoize_fun() {
+    let prog = "
+        (define calls 0)
+        (define slow-square (memoize (lambda (x) (set! calls (+ calls 1)) (* x x))))
+        (equal?
+            (list (slow-square 3) (slow-square 3) (slow-square 4) (slow-square 3) calls)
+            '(9 9 16 9 2))";
+
+    assert_eq!(eval(prog).unwrap(), environment::s_true());
+
+    //Argument lists are compared with equal?, so new but equal lists hit the cache.
+    let prog = "
+        (define calls 0)
+        (define record (memoize (lambda lst (set! calls (+ calls 1)) lst)))
+        (define results (map (lambda (n) (record 1 (list n) \"s\")) '(2 2 3 2)))
+        (= calls 2)";
+    assert!(eval_is_true(prog));
+}
+
+fn eval_to_string(prog: &str) -> String {
+    eval(prog).unwrap().into_string().unwrap().to_string()
+}
+
+#[test]
+fn procedure_name() {
+    assert_eq!(eval_to_string("($procedure-name car)"), "car");
+    assert_eq!(eval_to_string("($procedure-name +)"), "+");
+    assert_eq!(
+        eval_to_string("(define (named-fn x) x) ($procedure-name named-fn)"),
+        "named-fn"
+    );
+    assert_eq!(
+        eval("($procedure-name (lambda (x) x))").unwrap(),
+        environment::s_false()
+    );
+
+    let display = |object| {
+        eval_to_string(&format!(
+            "(define port (open-output-string)) (display {} port) (get-output-string port)",
+            object
+        ))
+    };
+    assert_eq!(display("car"), "#<procedure car>");
+    assert_eq!(display("(lambda (x) x)"), "#<procedure>");
+}
+
+#[test]
+fn number_to_string_radix() {
+    assert_eq!(eval_to_string("(number->string 1234)"), "1234");
+    assert_eq!(eval_to_string("(number->string 10 2)"), "1010");
+    assert_eq!(eval_to_string("(number->string 64 8)"), "100");
+    assert_eq!(eval_to_string("(number->string -99 10)"), "-99");
+    assert_eq!(eval_to_string("(number->string 255 16)"), "ff");
+    assert_eq!(eval_to_string("(number->string -255 16)"), "-ff");
+    assert_eq!(eval_to_string("(number->string 0 2)"), "0");
+    assert_eq!(
+        eval_to_string(&format!("(number->string {})", i64::MIN)),
+        i64::MIN.to_string()
+    );
+    assert!(eval("(number->string 10 3)").is_err());
+}
+
+#[test]
+fn hash_fun() {
+    assert_eq!(
+        eval("(= (equal-hash (list 1 2)) (equal-hash (list 1 2)))").unwrap(),
+        environment::s_true()
+    );
+    assert_eq!(
+        eval("(= (equal-hash (list \"a\" '(b))) (equal-hash (list (string-copy \"a\") '(b))))")
+            .unwrap(),
+        environment::s_true()
+    );
+    assert_eq!(
+        eval("(let ((x (cons 1 2))) (= (eq-hash x) (eqv-hash x) (eq-hash x)))").unwrap(),
+        environment::s_true()
+    );
+    assert_eq!(
+        eval("(= (eqv-hash 'sym) (eqv-hash 'sym))").unwrap(),
+        environment::s_true()
+    );
+
+    let hash = eval("(equal-hash '(1 \"two\" (3)))").unwrap();
+    assert_eq!(eval("(equal-hash '(1 \"two\" (3)))").unwrap(), hash);
+    assert!(hash.to_number().unwrap() >= 0);
+}
+
+#[test]
+fn lambda_body_sequence() {
+    assert_eq!(
+        eval("(let ((x 0)) ((lambda () (set! x 1) (set! x (+ x 1)) x)))").unwrap(),
+        SchemeType::Number(2)
+    );
+    assert_eq!(
+        eval("(let ((x 0)) ((lambda () (set! x 5) x (set! x 6))) x)").unwrap(),
+        SchemeType::Number(6)
+    );
+    assert_eq!(
+        eval("((lambda (x) (define y (* x 2)) (set! x (+ x y)) x) 3)").unwrap(),
+        SchemeType::Number(9)
+    );
+}
+
+#[test]
+fn cond_arrow() {
+    assert_eq!(
+        eval("(cond ((assoc 2 '((1 . a) (2 . b))) => cdr) (else #f))").unwrap(),
+        eval("'b").unwrap()
+    );
+    assert_eq!(
+        eval("(cond ((assoc 3 '((1 . a) (2 . b))) => cdr) (else #f))").unwrap(),
+        environment::s_false()
+    );
+}
+
+#[test]
+fn cond_tail_call() {
+    let prog = "
+        (define (ping n)
+            (cond
+                ((= n 0) 'ping)
+                (else (pong (- n 1)))))
+        (define (pong n)
+            (cond
+                ((= n 0) 'pong)
+                ((- n 1) => ping)))
+        (ping 100001)";
+
+    assert_eq!(eval(prog).unwrap(), eval("'pong").unwrap());
+}
+
+fn eval_is_true(prog: &str) -> bool {
+    eval(prog).unwrap() == environment::s_true()
+}
+
+#[test]
+fn map_fun() {
+    assert!(eval_is_true("(equal? (map - '(1 2 3)) '(-1 -2 -3))"));
+    assert!(eval_is_true("(equal? (map + '(1 2 3) '(10 20)) '(11 22))"));
+    assert!(eval_is_true("(null? (map + '()))"));
+}
+
+#[test]
+fn append_fun() {
+    assert!(eval_is_true(
+        "(equal? (append '(1) '() '(2 3) '(4)) '(1 2 3 4))"
+    ));
+    assert!(eval_is_true("(equal? (append '(1) 2) '(1 . 2))"));
+    assert!(eval("(append '(1 . 2) '(3))").is_err());
+}
+
+#[test]
+fn degenerate_list_construction() {
+    assert!(eval_is_true("(null? (append))"));
+    assert!(eval_is_true("(null? (list))"));
+    assert!(eval_is_true("(let ((x (list 1 2))) (eq? (append x) x))"));
+    assert!(eval_is_true("(equal? (append '(1 . 2)) '(1 . 2))"));
+    assert_eq!(eval("(append 5)").unwrap(), SchemeType::Number(5));
+    assert!(eval_is_true("(equal? (append '() 5) 5)"));
+
+    assert!(eval_is_true("(equal? (cons* 1 2 '(3 4)) '(1 2 3 4))"));
+    assert!(eval_is_true("(equal? (cons* 1 2) '(1 . 2))"));
+    assert_eq!(eval("(cons* 5)").unwrap(), SchemeType::Number(5));
+    assert!(eval_is_true("(equal? (apply list* 1 '(2 (3))) '(1 2 3))"));
+    assert!(eval("(cons*)").is_err());
+}
+
+#[test]
+fn append_map() {
+    assert!(eval_is_true(
+        "(equal? (append-map (lambda (x) (list x x)) (list 1 2)) '(1 1 2 2))"
+    ));
+    assert!(eval_is_true(
+        "(null? (append-map (lambda (x) (list x x)) '()))"
+    ));
+    assert!(eval_is_true(
+        "(null? (append-map (lambda (x) '()) (list 1 2 3)))"
+    ));
+    assert!(eval_is_true(
+        "(equal? (append-map list '(1 2) '(a b)) '(1 a 2 b))"
+    ));
+}
+
+#[test]
+fn flatten_fun() {
+    assert!(eval_is_true(
+        "(equal? (flatten '(1 (2 (3 ())) ((4)) 5)) '(1 2 3 4 5))"
+    ));
+    assert!(eval_is_true("(null? (flatten '(() (()))))"));
+}
+
+#[test]
+fn count_fun() {
+    assert_eq!(
+        eval("(count even? (list 1 2 3 4))").unwrap(),
+        SchemeType::Number(2)
+    );
+    assert_eq!(
+        eval("(count even? (list 1 3 5))").unwrap(),
+        SchemeType::Number(0)
+    );
+    assert_eq!(eval("(count even? '())").unwrap(), SchemeType::Number(0));
+    assert!(eval("(count car (list 1 2))").is_err());
+}
+
+#[test]
+fn find_fun() {
+    assert_eq!(
+        eval("(find even? (list 1 3 4 6))").unwrap(),
+        SchemeType::Number(4)
+    );
+    assert_eq!(
+        eval("(find even? (list 1 3 5))").unwrap(),
+        environment::s_false()
+    );
+    assert!(eval("(find car (list 1 2))").is_err());
+}
+
+fn eval_err(prog: &str) -> String {
+    format!("{:?}", eval(prog).unwrap_err())
+}
+
+#[test]
+fn bad_let_binding() {
+    assert!(eval_err("(let ((x)) x)").contains("let binding is missing an init expression"));
+    assert!(eval_err("(letrec ((x)) x)").contains("letrec binding is missing"));
+    assert!(eval_err("(let ((x 1 2)) x)").contains("more than one init expression"));
+    assert!(eval_err("(let ((1 2)) 1)").contains("let needs a symbol got a number"));
+    assert!(eval_err("(let* ((y 1) (\"x\" 2)) y)").contains("needs a symbol got a string"));
+    assert!(eval_err("(let (x) x)").contains("must be a list of a name and an init expression"));
+    assert!(eval_err("(letrec* (5) 1)").contains("got a number"));
+    assert!(eval_err("(let loop ((i)) i)").contains("missing an init expression"));
+    assert!(eval_err("(let ())").contains("let expected 2 or more arguments got 1"));
+}
+
+#[test]
+fn numeric_predicates() {
+    for pred in &[
+        "complex?",
+        "real?",
+        "rational?",
+        "integer?",
+        "exact-integer?",
+        "exact?",
+    ] {
+        assert!(eval_is_true(&format!("({} 2)", pred)));
+        assert!(eval_is_true(&format!("({} -7)", pred)));
+    }
+    for pred in &[
+        "complex?",
+        "real?",
+        "rational?",
+        "integer?",
+        "exact-integer?",
+    ] {
+        assert!(eval_is_true(&format!("(not ({} \"2\"))", pred)));
+        assert!(eval_is_true(&format!("(not ({} 'a))", pred)));
+    }
+    assert!(eval_is_true("(not (inexact? 2))"));
+    assert!(eval("(exact? \"2\")").is_err());
+    assert!(eval("(inexact? 'a)").is_err());
+}
+
+#[test]
+fn exact_nonnegative_integer() {
+    assert!(eval_is_true("(exact-nonnegative-integer? 0)"));
+    assert!(eval_is_true("(exact-nonnegative-integer? 42)"));
+    assert!(eval_is_true("(not (exact-nonnegative-integer? -1))"));
+    //There are no reals yet, so check other non-integers instead.
+    assert!(eval_is_true("(not (exact-nonnegative-integer? \"1\"))"));
+    assert!(eval_is_true("(not (exact-nonnegative-integer? #\\1))"));
+}
+
+#[test]
+fn char_literal() {
+    assert!(eval_is_true("(char? #\\a)"));
+    assert!(eval_is_true("(eqv? (string-ref \"A b\" 1) #\\space)"));
+    assert_eq!(eval("#\\x3bb").unwrap(), SchemeType::Char('λ'));
+}
+
+#[test]
+fn square_fun() {
+    assert_eq!(eval("(square -4)").unwrap(), SchemeType::Number(16));
+    assert_eq!(eval("(square 0)").unwrap(), SchemeType::Number(0));
+}
+
+#[test]
+fn expt_fun() {
+    assert_eq!(eval("(expt 3 4)").unwrap(), SchemeType::Number(81));
+    assert_eq!(eval("(expt 5 0)").unwrap(), SchemeType::Number(1));
+    assert_eq!(eval("(expt -2 3)").unwrap(), SchemeType::Number(-8));
+    assert_eq!(eval("(expt 2 62)").unwrap(), SchemeType::Number(1 << 62));
+    assert!(eval("(expt 2 -1)").is_err());
+
+    //Would take far too long if each multiplication was done one at a time.
+    assert_eq!(
+        eval("(expt 1 1000000000000000000)").unwrap(),
+        SchemeType::Number(1)
+    );
+    assert_eq!(
+        eval("(expt -1 1000000000000000001)").unwrap(),
+        SchemeType::Number(-1)
+    );
+}
+
+#[test]
+fn read_line() {
+    assert_eq!(
+        eval_to_string("(read-line (open-input-string \"hello\nworld\"))"),
+        "hello"
+    );
+    assert_eq!(
+        eval_to_string(
+            "(define port (open-input-string \"hello\nworld\")) (read-line port) (read-line port)"
+        ),
+        "world"
+    );
+    assert_eq!(
+        eval_to_string("(define port (open-input-string \"\nrest\")) (read-line port)"),
+        ""
+    );
+    assert_eq!(
+        eval_to_string(
+            "(define port (open-input-string \"a\n\nb\")) (read-line port) (read-line port)"
+        ),
+        ""
+    );
+    assert!(eval_is_true(
+        "(eof-object? (read-line (open-input-string \"\")))"
+    ));
+    assert!(eval_is_true(
+        "(define port (open-input-string \"last\n\")) (read-line port) (eof-object? (read-line port))"
+    ));
+    assert!(eval("(read-line \"not a port\")").is_err());
+}
+
+#[test]
+fn profile_report() {
+    let prog = "
+        (define (count-down n) (if (zero? n) 'done (count-down (- n 1))))
+        (profile-start!)
+        (count-down 10)
+        (profile-stop!)
+        (count-down 10)
+        (cdr (assoc 'count-down (profile-report)))";
+    assert_eq!(eval(prog).unwrap(), SchemeType::Number(11));
+
+    //Starting again throws away the old counts.
+    assert!(eval_is_true(
+        "(profile-start!) (profile-stop!) (not (assoc 'count-down (profile-report)))"
+    ));
+}
+
+#[test]
+fn program_without_value() {
+    let unspecified = eval("(if #f #f)").unwrap();
+    assert_eq!(eval("").unwrap(), unspecified);
+    assert_eq!(eval("  ; only a comment\n").unwrap(), unspecified);
+    assert_eq!(eval("(write-char #\\a)").unwrap(), unspecified);
+    assert_eq!(eval("(define x 1)").unwrap(), unspecified);
+    assert_eq!(eval("(define x 1) x").unwrap(), SchemeType::Number(1));
+}
+
+#[test]
+fn vector_copy() {
+    assert!(eval_is_true(
+        "(equal? (vector->list (vector-copy #(1 2 3 4) 1 3)) '(2 3))"
+    ));
+    assert!(eval_is_true(
+        "(define v #(1 2 3))
+        (define copy (vector-copy v))
+        (vector-set! copy 0 'a)
+        (and (equal? (vector->list copy) '(a 2 3)) (equal? (vector->list v) '(1 2 3)))"
+    ));
+    assert!(eval("(vector-copy #(1 2 3) 2 1)").is_err());
+    assert!(eval("(vector-copy #(1 2 3) 0 4)").is_err());
+}
+
+#[test]
+fn vector_copy_overlapping() {
+    assert!(eval_is_true(
+        "(define v (vector 1 2 3 4 5))
+        (vector-copy! v 1 v 0 3)
+        (equal? (vector->list v) '(1 1 2 3 5))"
+    ));
+    assert!(eval_is_true(
+        "(define v (vector 1 2 3 4 5))
+        (vector-copy! v 0 v 2)
+        (equal? (vector->list v) '(3 4 5 4 5))"
+    ));
+    assert!(eval("(vector-copy! (vector 1 2) 1 #(1 2))").is_err());
+}
+
+#[test]
+fn string_vector_conversion() {
+    assert!(eval_is_true(
+        "(equal? (vector->list (string->vector \"abc\")) '(#\\a #\\b #\\c))"
+    ));
+    assert!(eval_is_true(
+        "(equal? (vector->list (string->vector \"abcde\" 1 3)) '(#\\b #\\c))"
+    ));
+    assert_eq!(eval_to_string("(vector->string #(#\\a #\\b #\\c))"), "abc");
+    assert_eq!(eval_to_string("(vector->string #(#\\a #\\b #\\c) 1)"), "bc");
+    assert_eq!(eval_to_string("(vector->string #(#\\a #\\b) 1 1)"), "");
+    assert!(eval("(vector->string #(#\\a 1))").is_err());
+    assert!(eval("(string->vector \"abc\" 2 4)").is_err());
+}
+
+#[test]
+fn foldcase() {
+    assert_eq!(eval("(char-foldcase #\\A)").unwrap(), SchemeType::Char('a'));
+    assert_eq!(eval("(char-foldcase #\\a)").unwrap(), SchemeType::Char('a'));
+    assert_eq!(eval("(char-foldcase #\\1)").unwrap(), SchemeType::Char('1'));
+    assert!(eval_is_true("(char-ci=? #\\Q #\\q)"));
+    assert!(!eval_is_true("(char-ci=? #\\Q #\\r)"));
+
+    assert_eq!(eval_to_string("(string-foldcase \"HELLO\")"), "hello");
+    assert_eq!(
+        eval_to_string("(string-foldcase \"MiXeD cAsE ΣΑΣ\")"),
+        "mixed case σασ"
+    );
+    //Folding an already folded string changes nothing.
+    assert_eq!(
+        eval_to_string("(string-foldcase (string-foldcase \"MiXeD cAsE ΣΑΣ\"))"),
+        eval_to_string("(string-foldcase \"MiXeD cAsE ΣΑΣ\")")
+    );
+    //Full folding can change the length of a string but simple folding can not.
+    assert_eq!(eval_to_string("(string-foldcase \"İ\")"), "i\u{307}");
+    assert_eq!(eval("(char-foldcase #\\İ)").unwrap(), SchemeType::Char('İ'));
+}
+
+#[test]
+fn guard_fun() {
+    assert!(eval_is_true(
+        "(equal? (guard (e ((symbol? e) (list 'caught e)) ((string? e) 'string)) (raise 'boom))
+            '(caught boom))"
+    ));
+    assert!(eval_is_true(
+        "(equal? (guard (e ((symbol? e) 'symbol) (else (list 'else e))) (raise 42)) '(else 42))"
+    ));
+    assert!(eval_is_true("(eqv? (guard (e ((symbol? e) 'symbol)) 5) 5)"));
+    assert_eq!(
+        eval_to_string("(guard (e ((error-object? e) (error-object-message e))) (car 1))"),
+        "Not a pair."
+    );
+    assert!(eval_is_true(
+        "(equal? (guard (e ((error-object? e) (error-object-irritants e)))
+                    (error 'who \"Message.\" 1 2))
+                '(1 2))"
+    ));
+}
+
+#[test]
+fn guard_builtin_errors() {
+    assert_eq!(
+        eval_to_string("(guard (e ((error-object? e) (error-object-message e))) (quotient 1 0))"),
+        "Division by zero."
+    );
+    assert_eq!(
+        eval_to_string(
+            "(guard (e ((error-object? e) (error-object-message e))) (vector-ref (vector) 3))"
+        ),
+        "Argument out of bounds."
+    );
+    assert_eq!(
+        eval_to_string("(guard (e ((error-object? e) (error-object-message e))) (5 1))"),
+        "Cannot call a number, which is not a procedure."
+    );
+    assert!(eval_is_true(
+        "(null? (guard (e ((error-object? e) (error-object-irritants e))) (quotient 1 0)))"
+    ));
+
+    //Handlers from with-exception-handler see them too.
+    assert_eq!(
+        eval_to_string(
+            "(define seen #f)
+            (guard (e (#t seen))
+                (with-exception-handler
+                    (lambda (e) (set! seen (error-object-message e)) 0)
+                    (lambda () (quotient 1 0))))"
+        ),
+        "Division by zero."
+    );
+
+    //Without a handler the original error comes out.
+    assert!(matches!(
+        eval("(quotient 1 0)"),
+        Err(RuntimeError::DivByZero)
+    ));
+}
+
+#[test]
+fn describe_uncaught() {
+    let describe = |prog| eval(prog).unwrap_err().to_string();
+
+    assert_eq!(
+        describe("(error 'who \"Bad thing:\" 1 \"two\" 'three)"),
+        "who: Bad thing: 1 \"two\" three"
+    );
+    assert_eq!(describe("(error \"Plain.\")"), "Plain.");
+    assert_eq!(describe("(raise 'boom)"), "Uncaught raise of boom.");
+    assert_eq!(describe("(quotient 1 0)"), "Division by zero.");
+    assert_eq!(
+        describe("(undefined-name)"),
+        "undefined-name is not defined."
+    );
+}
+
+#[test]
+fn guard_reraise() {
+    assert!(matches!(
+        eval("(guard (e ((string? e) 'string)) (raise 'unmatched))"),
+        Err(RuntimeError::Raise(_))
+    ));
+    assert!(eval_is_true(
+        "(equal? (guard (outer (#t (list 'outer outer)))
+                    (guard (inner ((string? inner) 'string)) (raise 7)))
+                '(outer 7))"
+    ));
+}
+
+#[test]
+fn raise_continuable() {
+    assert_eq!(
+        eval(
+            "(with-exception-handler
+                (lambda (e) (* e 2))
+                (lambda () (+ 1 (raise-continuable 20))))"
+        )
+        .unwrap(),
+        SchemeType::Number(41)
+    );
+    //The outer handler is current while the inner one runs.
+    assert!(eval_is_true(
+        "(equal?
+            (with-exception-handler
+                (lambda (e) (list 'outer e))
+                (lambda ()
+                    (with-exception-handler
+                        (lambda (e) (raise-continuable (list 'inner e)))
+                        (lambda () (raise-continuable 1)))))
+            '(outer (inner 1)))"
+    ));
+    //The inner handler is uninstalled once with-exception-handler returns.
+    assert!(eval_is_true(
+        "(equal?
+            (with-exception-handler
+                (lambda (e) 'outer)
+                (lambda ()
+                    (with-exception-handler (lambda (e) 'inner) (lambda () 1))
+                    (raise-continuable 'x)))
+            'outer)"
+    ));
+}
+
+#[test]
+fn raise_handler_returns() {
+    assert_eq!(
+        eval_to_string(
+            "(guard (e ((error-object? e) (error-object-message e)))
+                (with-exception-handler (lambda (e) 0) (lambda () (raise 'oops))))"
+        ),
+        "Handler returned from a non-continuable raise."
+    );
+    assert!(matches!(
+        eval("(with-exception-handler (lambda (e) 0) (lambda () (raise 'oops)))"),
+        Err(RuntimeError::Raise(_))
+    ));
+}
+
+#[test]
+fn empty_strings() {
+    assert!(eval_is_true("(string-null? \"\")"));
+    assert!(!eval_is_true("(string-null? \"a\")"));
+    assert!(eval("(string-null? 'a)").is_err());
+
+    assert!(eval("(string-ref \"\" 0)").is_err());
+    assert!(eval("(string-ref \"abc\" 3)").is_err());
+
+    assert_eq!(eval_to_string("(substring \"abc\" 0 2)"), "ab");
+    assert_eq!(eval_to_string("(substring \"abc\" 1 1)"), "");
+    assert_eq!(eval_to_string("(substring \"abc\" 3 3)"), "");
+    assert_eq!(eval_to_string("(substring \"\" 0 0)"), "");
+    assert!(eval("(substring \"abc\" 2 1)").is_err());
+    assert!(eval("(substring \"abc\" 3 4)").is_err());
+}
+
+#[test]
+fn fluid_let() {
+    assert!(eval_is_true(
+        "(define x 1)
+        (define (get-x) x)
+        (equal? (list (fluid-let ((x 2)) (get-x)) (get-x)) '(2 1))"
+    ));
+    assert!(eval_is_true(
+        "(define x 1)
+        (define y 'a)
+        (fluid-let ((x 2) (y 'b)) (set! x 3))
+        (and (eqv? x 1) (eqv? y 'a))"
+    ));
+    //The original value comes back when the body is escaped by a raise.
+    assert!(eval_is_true(
+        "(define x 1)
+        (define (get-x) x)
+        (equal? (guard (e (#t (list e (get-x)))) (fluid-let ((x 2)) (raise (get-x)))) '(2 1))"
+    ));
+    assert!(eval("(fluid-let ((undefined-variable 1)) 1)").is_err());
+}
+
+#[test]
+fn modexp() {
+    assert_eq!(eval("(modexp 4 13 497)").unwrap(), SchemeType::Number(445));
+    assert_eq!(eval("(modexp 4 0 497)").unwrap(), SchemeType::Number(1));
+    assert_eq!(eval("(modexp 4 0 1)").unwrap(), SchemeType::Number(0));
+    assert_eq!(eval("(modexp -2 3 5)").unwrap(), SchemeType::Number(2));
+    //Would overflow if the squares were done in 64 bits.
+    assert_eq!(
+        eval("(modexp 3037000500 2 9223372036854775807)").unwrap(),
+        SchemeType::Number(145_474_193)
+    );
+    assert_eq!(
+        eval("(modexp 123456789 65537 9223372036854775783)").unwrap(),
+        SchemeType::Number(8_263_562_239_862_272_788)
+    );
+    assert!(eval("(modexp 2 -1 5)").is_err());
+    assert!(eval("(modexp 2 1 0)").is_err());
+}
+
+#[test]
+fn output_string_port() {
+    assert_eq!(
+        eval_to_string("(get-output-string (open-output-string))"),
+        ""
+    );
+
+    let prog = "
+        (define port (open-output-string))
+        (let loop ((i 0))
+            (if (< i 1000)
+                (begin
+                    (write-char (string-ref \"abc\" (remainder i 3)) port)
+                    (loop (+ i 1)))))
+        (get-output-string port)";
+    let expected = "abc".repeat(334)[..1000].to_string();
+    assert_eq!(eval_to_string(prog), expected);
+
+    assert!(eval("(write-char 1 (open-output-string))").is_err());
+    assert!(eval("(write-char #\\a (open-input-string \"\"))").is_err());
+}
+
+#[test]
+fn dotimes() {
+    assert_eq!(
+        eval("(let ((s 0)) (dotimes (i 5) (set! s (+ s i))) s)").unwrap(),
+        SchemeType::Number(10)
+    );
+    assert_eq!(
+        eval("(let ((s 0)) (dotimes (i 0) (set! s (+ s 1))) s)").unwrap(),
+        SchemeType::Number(0)
+    );
+    assert_eq!(
+        eval("(let ((s 0)) (dotimes (i -3) (set! s (+ s 1))) s)").unwrap(),
+        SchemeType::Number(0)
+    );
+    //The count is only evaluated once and the loop still works with + rebound.
+    assert_eq!(
+        eval(
+            "(let ((s 0) (n 3))
+                (dotimes (i n) (set! n 100) (set! s (+ s 1)))
+                (let ((+ -)) (dotimes (i 2) (set! s (+ s 1))))
+                s)"
+        )
+        .unwrap(),
+        SchemeType::Number(1)
+    );
+}
+
+#[test]
+fn symbol_append() {
+    assert!(eval_is_true("(eq? (symbol-append 'foo '- 'bar) 'foo-bar)"));
+    assert!(eval_is_true("(eq? (symbol-append 'foo) 'foo)"));
+    assert_eq!(
+        eval_to_string("(symbol->string (symbol-append 'a 'b 'c))"),
+        "abc"
+    );
+    assert!(eval_is_true(
+        "(eq? (symbol-append 'x 'y) (symbol-append 'x 'y))"
+    ));
+    assert!(eval("(symbol-append 'foo \"bar\")").is_err());
+    assert!(eval("(symbol-append 'foo '(bar))").is_err());
+}
+
+#[test]
+fn bytevector_literal() {
+    assert!(eval_is_true("(bytevector? #u8(1 2 255))"));
+    assert!(eval_is_true("(bytevector? '#u8())"));
+    assert!(!eval_is_true("(bytevector? #(1 2))"));
+    assert!(eval_is_true(
+        "(= (equal-hash #u8(1 2 255)) (equal-hash #u8(1 2 255)))"
+    ));
+}
+
+#[test]
+fn bytevector_accessors() {
+    assert_eq!(
+        eval("(bytevector-u8-ref (bytevector 10 20 30) 1)").unwrap(),
+        SchemeType::Number(20)
+    );
+    assert_eq!(
+        eval("(bytevector-length (bytevector))").unwrap(),
+        SchemeType::Number(0)
+    );
+    assert_eq!(
+        eval("(bytevector-length #u8(1 2 3))").unwrap(),
+        SchemeType::Number(3)
+    );
+    assert_eq!(
+        eval("(let ((b (bytevector 1 2))) (bytevector-u8-set! b 0 255) (bytevector-u8-ref b 0))")
+            .unwrap(),
+        SchemeType::Number(255)
+    );
+
+    assert!(eval("(bytevector-u8-ref (bytevector 1 2) 2)").is_err());
+    assert!(eval("(bytevector-u8-ref (bytevector 1 2) -1)").is_err());
+    assert!(eval("(bytevector-u8-set! (bytevector 1 2) 2 0)").is_err());
+    assert!(eval("(bytevector-u8-set! (bytevector 1 2) 0 256)").is_err());
+    assert!(eval("(bytevector-u8-set! (bytevector 1 2) 0 -1)").is_err());
+    assert!(eval("(bytevector 1 256)").is_err());
+    //Literals are constant.
+    assert!(eval("(bytevector-u8-set! #u8(1 2) 0 0)").is_err());
+}
+
+#[test]
+fn make_bytevector() {
+    assert_eq!(
+        eval("(bytevector-u8-ref (make-bytevector 3) 2)").unwrap(),
+        SchemeType::Number(0)
+    );
+    assert_eq!(
+        eval("(bytevector-u8-ref (make-bytevector 3 7) 2)").unwrap(),
+        SchemeType::Number(7)
+    );
+    assert_eq!(
+        eval("(bytevector-length (make-bytevector 0 7))").unwrap(),
+        SchemeType::Number(0)
+    );
+    assert!(eval("(make-bytevector 3 300)").is_err());
+    assert!(eval("(make-bytevector -1)").is_err());
+}
+
+#[test]
+fn utf8_conversion() {
+    assert_eq!(
+        eval_to_string("(utf8->string (string->utf8 \"héllo\"))"),
+        "héllo"
+    );
+    assert_eq!(
+        eval("(bytevector-length (string->utf8 \"héllo\"))").unwrap(),
+        SchemeType::Number(6)
+    );
+    assert_eq!(
+        eval("(bytevector-length (string->utf8 \"héllo\" 1 2))").unwrap(),
+        SchemeType::Number(2)
+    );
+    assert_eq!(eval_to_string("(utf8->string #u8(97 98 99) 1)"), "bc");
+    assert_eq!(eval_to_string("(utf8->string #u8(206 187))"), "λ");
+    assert!(eval("(string->utf8 \"abc\" 2 4)").is_err());
+
+    assert!(eval_is_true(
+        "(guard (e ((error-object? e) (string? (error-object-message e))))
+            (utf8->string #u8(255 254)))"
+    ));
+    //Splitting a multibyte character leaves invalid UTF-8.
+    assert!(eval("(utf8->string (string->utf8 \"é\") 0 1)").is_err());
+}
+
+#[test]
+fn display_to_port() {
+    let prog = "
+        (define port (open-output-string))
+        (display '(1 \"two\" #\\3 #(4) #u8(5)) port)
+        (newline port)
+        (get-output-string port)";
+    assert_eq!(eval_to_string(prog), "(1 two 3 #(4) #u8(5))\n");
+}
+
+#[test]
+fn pretty_print() {
+    let pretty = |datum: &str, width: i64| {
+        eval_to_string(&format!(
+            "(define port (open-output-string))
+            (pretty-print '{} port {})
+            (get-output-string port)",
+            datum, width
+        ))
+    };
+
+    assert_eq!(pretty("(a (b c) d)", 80), "(a (b c) d)\n");
+    assert_eq!(pretty("(a (b c) d)", 11), "(a (b c) d)\n");
+    assert_eq!(
+        pretty("(define (f x) (if (zero? x) 1 (* x (f (- x 1)))))", 20),
+        "(define
+ (f x)
+ (if
+  (zero? x)
+  1
+  (* x (f (- x 1)))))
+"
+    );
+    assert_eq!(pretty("(a b . c)", 5), "(a\n b\n . c)\n");
+    assert_eq!(
+        pretty("(\"a b\" #\\c (\"x\" \"y\" \"z\"))", 80),
+        "(\"a b\" #\\c (\"x\" \"y\" \"z\"))\n"
+    );
+    assert_eq!(
+        pretty("(\"a b\" #\\c (\"x\" \"y\" \"z\"))", 10),
+        "(\"a b\"\n #\\c\n (\"x\"\n  \"y\"\n  \"z\"))\n"
+    );
+
+    //The port may be left out when giving a width.
+    assert!(eval_is_true("(begin (pretty-print '(a b) 10) #t)"));
+}
+
+#[test]
+fn eqv_identity() {
+    assert!(eval_is_true("(let ((p (cons 1 2))) (eqv? p p))"));
+    assert!(eval_is_true("(not (eqv? (cons 1 2) (cons 1 2)))"));
+    assert!(eval_is_true("(let ((v (vector 1 2))) (eqv? v v))"));
+    assert!(eval_is_true("(not (eqv? (vector 1 2) (vector 1 2)))"));
+    assert!(eval_is_true(
+        "(not (eqv? (make-bytevector 2 0) (make-bytevector 2 0)))"
+    ));
+
+    //Mutating one object is visible through every reference to it.
+    assert!(eval_is_true(
+        "(let* ((p (cons 1 2)) (q p)) (set-car! p 3) (and (eqv? p q) (= (car q) 3)))"
+    ));
+}
+
+#[test]
+fn equal_aggregates() {
+    assert!(eval_is_true("(equal? #(1 2) #(1 2))"));
+    assert!(eval_is_true(
+        "(equal? (vector 1 \"a\" '(b #(c))) #(1 \"a\" (b #(c))))"
+    ));
+    assert!(eval_is_true("(not (equal? #(1 2) #(1 2 3)))"));
+    assert!(eval_is_true("(not (equal? #(1 2) #(1 3)))"));
+    assert!(eval_is_true("(equal? #u8(1 2 3) (bytevector 1 2 3))"));
+    assert!(eval_is_true("(not (equal? #u8(1 2) #u8(1 3)))"));
+    assert!(eval_is_true("(equal? \"abc\" (string-copy \"abc\"))"));
+    assert!(eval_is_true("(not (equal? \"abc\" \"abd\"))"));
+
+    assert!(eval_is_true("(not (equal? #(1 2) '(1 2)))"));
+    assert!(eval_is_true("(not (equal? #u8(1 2) #(1 2)))"));
+
+    assert!(eval_is_true(
+        "(= (equal-hash #(1 (2))) (equal-hash (vector 1 (list 2))))"
+    ));
+}
+
+#[test]
+fn display_limited() {
+    let limited = |datum: &str, depth: &str, length: &str| {
+        eval_to_string(&format!(
+            "(define port (open-output-string))
+            (display-limited '{} {} {} port)
+            (get-output-string port)",
+            datum, depth, length
+        ))
+    };
+
+    assert_eq!(limited("(1 2 3 4 5)", "#f", "3"), "(1 2 3 ...)");
+    assert_eq!(limited("(1 2 3)", "#f", "3"), "(1 2 3)");
+    assert_eq!(limited("(1 2)", "#f", "0"), "(...)");
+    assert_eq!(limited("#(1 2 3 4)", "#f", "2"), "#(1 2 ...)");
+    assert_eq!(limited("#u8(1 2 3 4)", "#f", "2"), "#u8(1 2 ...)");
+    assert_eq!(limited("(1 2 . 3)", "#f", "5"), "(1 2 . 3)");
+
+    assert_eq!(limited("(1 (2 (3 (4))))", "2", "#f"), "(1 (2 ...))");
+    assert_eq!(limited("(1 #(2 (3)))", "2", "#f"), "(1 #(2 ...))");
+    assert_eq!(limited("(a b)", "0", "#f"), "...");
+    assert_eq!(limited("(1 (2 (3 (4))))", "#f", "#f"), "(1 (2 (3 (4))))");
+
+    assert_eq!(
+        limited("((1 2 3) (4 (5 6 7)) 8 9)", "2", "2"),
+        "((1 2 ...) (4 ...) ...)"
+    );
+}
+
+#[test]
+fn min_max() {
+    assert_eq!(eval("(max 1 2 3)").unwrap(), SchemeType::Number(3));
+    assert_eq!(eval("(max 3 -2)").unwrap(), SchemeType::Number(3));
+    assert_eq!(eval("(min 3 1 2)").unwrap(), SchemeType::Number(1));
+    assert_eq!(eval("(min 5)").unwrap(), SchemeType::Number(5));
+
+    assert!(eval("(max)").is_err());
+    assert!(eval("(min)").is_err());
+    assert!(eval("(max 'a)").is_err());
+    assert!(eval("(min 1 'a)").is_err());
+}
+
+#[test]
+fn digit_value() {
+    assert_eq!(eval("(digit-value #\\7)").unwrap(), SchemeType::Number(7));
+    assert_eq!(eval("(digit-value #\\0)").unwrap(), SchemeType::Number(0));
+    assert!(eval_is_true("(not (digit-value #\\a))"));
+    assert!(eval_is_true("(not (digit-value #\\/))"));
+    assert!(eval_is_true("(not (digit-value #\\:))"));
+
+    //Arabic-Indic, Devanagari, fullwidth and mathematical bold digits.
+    assert_eq!(
+        eval("(digit-value #\\x663)").unwrap(),
+        SchemeType::Number(3)
+    );
+    assert_eq!(
+        eval("(digit-value #\\x96F)").unwrap(),
+        SchemeType::Number(9)
+    );
+    assert_eq!(
+        eval("(digit-value #\\xFF15)").unwrap(),
+        SchemeType::Number(5)
+    );
+    assert_eq!(
+        eval("(digit-value #\\x1D7D0)").unwrap(),
+        SchemeType::Number(2)
+    );
+    //Numeric but not a decimal digit.
+    assert!(eval_is_true("(not (digit-value #\\x2155))"));
+
+    assert!(eval("(digit-value 7)").is_err());
+}
+
+#[test]
+fn string_map() {
+    assert_eq!(eval_to_string("(string-map char-upcase \"abc\")"), "ABC");
+    assert_eq!(eval_to_string("(string-map char-downcase \"AbÇ\")"), "abç");
+    assert_eq!(eval_to_string("(string-map char-upcase \"\")"), "");
+    assert_eq!(
+        eval_to_string("(string-map (lambda (a b) (if (eqv? a #\\-) b a)) \"a-c-e\" \"xbydzzz\")"),
+        "abcde"
+    );
+    assert!(eval("(string-map (lambda (c) 1) \"abc\")").is_err());
+    assert!(eval("(string-map char-upcase 'abc)").is_err());
+}
+
+#[test]
+fn string_for_each() {
+    let prog = "
+        (define port (open-output-string))
+        (string-for-each (lambda (c) (write-char (char-upcase c) port)) \"abc\")
+        (string-for-each (lambda (a b) (write-char a port) (write-char b port)) \"xyz\" \"12\")
+        (get-output-string port)";
+    assert_eq!(eval_to_string(prog), "ABCx1y2");
+}
+
+#[test]
+fn dynamic_wind() {
+    let prog = "
+        (define trace '())
+        (define (note x) (set! trace (cons x trace)))
+        (define result
+            (dynamic-wind
+                (lambda () (note 'before))
+                (lambda ()
+                    (let loop ((i 0))
+                        (if (< i 100000)
+                            (loop (+ i 1))
+                            i)))
+                (lambda () (note 'after))))
+        (and (= result 100000) (equal? trace '(after before)))";
+    assert!(eval_is_true(prog));
+
+    let prog = "
+        (define afters 0)
+        (define caught
+            (guard (e (#t e))
+                (dynamic-wind
+                    (lambda () #f)
+                    (lambda () (raise 'oops))
+                    (lambda () (set! afters (+ afters 1))))))
+        (and (eq? caught 'oops) (= afters 1))";
+    assert!(eval_is_true(prog));
+}
+
+#[test]
+fn list_to_string() {
+    assert_eq!(
+        eval_to_string("(list->string (list #\\a #\\b #\\c))"),
+        "abc"
+    );
+    assert_eq!(eval_to_string("(list->string '())"), "");
+
+    let long = "abcdefghij".repeat(10);
+    assert_eq!(
+        eval_to_string(&format!(
+            "(list->string (vector->list (string->vector \"{}\")))",
+            long
+        )),
+        long
+    );
+
+    assert!(eval_is_true(
+        "(guard (e ((error-object? e)
+                    (and (equal? (error-object-message e) \"Element is not a character.\")
+                        (equal? (error-object-irritants e) '(1 1)))))
+            (list->string (list #\\a 1)))"
+    ));
+    assert!(eval("(list->string (cons #\\a #\\b))").is_err());
+}
+
+#[test]
+fn current_jiffy() {
+    assert_eq!(
+        eval("(jiffies-per-second)").unwrap(),
+        SchemeType::Number(1_000_000)
+    );
+
+    let prog = "
+        (define t0 (current-jiffy))
+        (define t1 (current-jiffy))
+        (and (>= t0 0) (>= t1 t0))";
+    assert!(eval_is_true(prog));
+}
+
+#[test]
+fn nested_apply() {
+    assert_eq!(
+        eval("(apply apply + (list (list 1 2 3)))").unwrap(),
+        SchemeType::Number(6)
+    );
+    assert!(eval_is_true(
+        "(equal? (apply map list (list (list 1 2) (list 3 4))) '((1 3) (2 4)))"
+    ));
+    assert_eq!(
+        eval("(apply (apply (lambda (x) (lambda (y) (* x y))) '(6)) '(7))").unwrap(),
+        SchemeType::Number(42)
+    );
+    assert_eq!(
+        eval("(apply + 1 2 '(3 4))").unwrap(),
+        SchemeType::Number(10)
+    );
+    assert_eq!(
+        eval("(apply apply (list + 1 '(2)))").unwrap(),
+        SchemeType::Number(3)
+    );
+
+    assert!(eval("(apply + 1)").is_err());
+    assert!(eval("(apply 1 '(2))").is_err());
+}
+
+#[test]
+fn hash_table_string_keys() {
+    assert!(eval_is_true(
+        "(and (= (equal-hash \"key\") (equal-hash (string-copy \"key\")))
+            (= (equal-hash #\\a) (equal-hash (string-ref \"a\" 0))))"
+    ));
+
+    let prog = "
+        (define h (make-hash-table))
+        (hash-table-set! h \"key\" 1)
+        (hash-table-set! h #\\c 2)
+        (and (= (hash-table-ref h (list->string (list #\\k #\\e #\\y))) 1)
+            (= (hash-table-ref h (string-ref \"abc\" 2)) 2))";
+    assert!(eval_is_true(prog));
+
+    let prog = "
+        (define h (make-hash-table eq?))
+        (hash-table-set! h \"key\" 1)
+        (eq? (hash-table-ref/default h (string-copy \"key\") 'missing) 'missing)";
+    assert!(eval_is_true(prog));
+}
+
+#[test]
+fn hash_table() {
+    let prog = "
+        (define h (make-hash-table))
+        (let loop ((i 0))
+            (if (< i 100)
+                (begin
+                    (hash-table-set! h (list i) (* i i))
+                    (loop (+ i 1)))))
+        (hash-table-set! h '(7) 'seven)
+        (hash-table-delete! h '(3))
+        (hash-table-delete! h '(1000))
+        (and (= (hash-table-count h) 99)
+            (eq? (hash-table-ref h '(7)) 'seven)
+            (= (hash-table-ref h '(99)) 9801)
+            (not (hash-table-contains? h '(3)))
+            (eq? (hash-table-ref h '(3) (lambda () 'gone)) 'gone)
+            (= (apply + (map car (hash-table-keys h))) (- 4950 3)))";
+    assert!(eval_is_true(prog));
+
+    assert!(eval("(hash-table-ref (make-hash-table) 1)").is_err());
+    assert!(eval("(hash-table-set! '() 1 2)").is_err());
+}
+
+#[test]
+fn promises() {
+    let prog = "
+        (define count 0)
+        (define p (delay (begin (set! count (+ count 1)) (* 6 7))))
+        (and (promise? p) (= count 0) (= (force p) 42) (= (force p) 42) (= count 1))";
+    assert!(eval_is_true(prog));
+
+    assert_eq!(
+        eval("(force (make-promise 5))").unwrap(),
+        SchemeType::Number(5)
+    );
+    assert!(eval_is_true(
+        "(let ((p (delay 1))) (eq? (make-promise p) p))"
+    ));
+    assert_eq!(eval("(force 7)").unwrap(), SchemeType::Number(7));
+    assert!(eval("(force (delay-force 1))").is_err());
+
+    //Forcing a promise from its own thunk gives the first value computed.
+    let prog = "
+        (define x 5)
+        (define p (delay (begin (set! x (+ x 1)) (if (> x 10) x (force p)))))
+        (and (= (force p) 11) (begin (set! x 20) (= (force p) 11)))";
+    assert!(eval_is_true(prog));
+}
+
+#[test]
+fn delay_force_chain() {
+    let prog = "
+        (define (countdown n)
+            (delay-force
+                (if (zero? n)
+                    (delay 'done)
+                    (countdown (- n 1)))))
+        (eq? (force (countdown 100000)) 'done)";
+    assert!(eval_is_true(prog));
+}
+
+#[test]
+fn streams() {
+    let prog = "
+        (define forced 0)
+        (define (integers-from n)
+            (cons-stream n (begin (set! forced (+ forced 1)) (integers-from (+ n 1)))))
+        (define s (integers-from 0))
+        (and (= (stream-ref s 5) 5)
+            (= forced 5)
+            (= (stream-ref s 3) 3)
+            (= forced 5)
+            (= (stream-car (stream-cdr s)) 1))";
+    assert!(eval_is_true(prog));
+
+    assert!(eval_is_true(
+        "(let ((cons list)) (stream-pair? (cons-stream 1 the-empty-stream)))"
+    ));
+    assert!(eval_is_true(
+        "(stream-null? (stream-cdr (cons-stream 1 the-empty-stream)))"
+    ));
+    assert!(eval("(stream-cdr '(1 2))").is_err());
+}
+
+#[test]
+fn procedure_arity() {
+    assert!(eval_is_true("(equal? (procedure-arity car) '(1 . 1))"));
+    assert!(eval_is_true("(equal? (procedure-arity +) '(0 . #f))"));
+    assert!(eval_is_true(
+        "(equal? (procedure-arity make-string) '(1 . 2))"
+    ));
+    assert!(eval_is_true(
+        "(equal? (procedure-arity (lambda (a . rest) a)) '(1 . #f))"
+    ));
+    assert!(eval_is_true(
+        "(equal? (procedure-arity (lambda args args)) '(0 . #f))"
+    ));
+}
+
+#[test]
+fn car_cdr_compositions() {
+    assert_eq!(eval("(cadr (list 1 2 3))").unwrap(), SchemeType::Number(2));
+    assert_eq!(eval("(caddr (list 1 2 3))").unwrap(), SchemeType::Number(3));
+    assert_eq!(eval("(caar '((1) 2))").unwrap(), SchemeType::Number(1));
+    assert_eq!(eval("(cadddr '(1 2 3 4))").unwrap(), SchemeType::Number(4));
+    assert!(eval_is_true("(equal? (cddr '(1 2 3)) '(3))"));
+    assert!(eval_is_true("(equal? (cdar '((1 2) 3)) '(2))"));
+    assert!(eval("(caddr (list 1 2))").is_err());
+    assert!(eval("(caar (list 1 2))").is_err());
+}
+
+#[test]
+fn positional_accessors() {
+    assert!(eval_is_true("(eqv? (third (list 'a 'b 'c 'd)) 'c)"));
+    assert_eq!(eval("(first '(1 2 3))").unwrap(), SchemeType::Number(1));
+    assert_eq!(
+        eval("(fifth '(1 2 3 4 5 6))").unwrap(),
+        SchemeType::Number(5)
+    );
+    assert_eq!(
+        eval("(tenth '(1 2 3 4 5 6 7 8 9 10))").unwrap(),
+        SchemeType::Number(10)
+    );
+    assert_eq!(
+        eval("(list-ref '(1 2 3) 1)").unwrap(),
+        SchemeType::Number(2)
+    );
+    assert!(eval_is_true("(equal? (list-tail '(1 2 3) 2) '(3))"));
+    assert!(eval("(fifth '(1 2 3 4))").is_err());
+    assert!(eval("(list-ref '(1 2 3) 3)").is_err());
+    assert!(eval("(list-tail '(1 2) 3)").is_err());
+}
+
+#[test]
+fn immutable_pairs() {
+    assert!(eval_is_true(
+        "(let ((p (cons 1 2))) (set-car! p 3) (set-cdr! p 4) (equal? p '(3 . 4)))"
+    ));
+    assert!(eval_is_true(
+        "(let ((lst (list 1 2))) (set-car! (cdr lst) 5) (equal? lst '(1 5)))"
+    ));
+    assert!(eval("(set-car! '(1 2 3) 9)").is_err());
+    assert!(eval("(set-cdr! (cdr '(1 2 3)) '())").is_err());
+    assert!(eval("(let ((lst '((1 2) 3))) (set-cdr! (car lst) 9))").is_err());
+    assert!(eval_is_true(
+        "(let ((lst '(1 2 3))) (guard (e (#t (equal? lst '(1 2 3)))) (set-car! lst 0)))"
+    ));
+}
+
+#[test]
+fn when_unless() {
+    assert_eq!(eval("(when #t 1 2)").unwrap(), SchemeType::Number(2));
+    assert_eq!(eval("(unless #f 1 2)").unwrap(), SchemeType::Number(2));
+    assert!(eval_is_true("(eqv? (when #f 1) (if #f #f))"));
+    assert!(eval_is_true("(eqv? (unless #t 1) (if #f #f))"));
+    assert_eq!(
+        eval("(let ((x 0)) (when (= x 0) (set! x 5)) (unless (= x 0) (set! x (* x 2))) x)")
+            .unwrap(),
+        SchemeType::Number(10)
+    );
+    assert!(eval("(when #t)").is_err());
+}
+
+#[test]
+fn list_index() {
+    assert_eq!(
+        eval("(list-index even? (list 1 3 4 5))").unwrap(),
+        SchemeType::Number(2)
+    );
+    assert!(eval_is_true("(not (list-index even? (list 1 3 5)))"));
+    assert!(eval_is_true("(not (list-index even? '()))"));
+    assert_eq!(
+        eval("(list-index < '(3 2 1 0) '(1 1 2 5))").unwrap(),
+        SchemeType::Number(2)
+    );
+    assert!(eval_is_true("(not (list-index = '(1 2 3) '(4 5)))"));
+}
+
+#[test]
+fn delete_duplicates() {
+    assert!(eval_is_true(
+        "(equal? (delete-duplicates (list 1 2 1 3 2)) '(1 2 3))"
+    ));
+    assert!(eval_is_true(
+        "(let* ((lst (list 1 2 3)) (res (delete-duplicates lst))) (and (equal? res lst) (not (eqv? res lst))))"
+    ));
+    assert!(eval_is_true(
+        "(equal? (delete-duplicates '(\"a\" \"b\" \"a\")) '(\"a\" \"b\"))"
+    ));
+    assert!(eval_is_true(
+        "(equal? (delete-duplicates '(1 2 3 4 5) (lambda (x y) (= (remainder x 2) (remainder y 2)))) '(1 2))"
+    ));
+    assert!(eval_is_true(
+        "(equal? (delete 2 (list 1 2 3 2 4)) '(1 3 4))"
+    ));
+    assert!(eval_is_true("(equal? (delete '(1) '((1) 2 (1))) '(2))"));
+    assert!(eval_is_true("(equal? (delete 5 '(1 2 3)) '(1 2 3))"));
+    assert!(eval_is_true("(equal? (delete 3 '(1 2 3 4) <) '(1 2 3))"));
+}
+
+#[test]
+fn define_in_expression() {
+    assert_eq!(
+        eval("(define (f) (define x 2) (define y 3) (* x y)) (f)").unwrap(),
+        SchemeType::Number(6)
+    );
+    let message = "define must come before any expressions";
+    assert!(eval_err("(+ 1 (define x 2))").contains(message));
+    assert!(eval_err("(let () (display 1) (define x 2) x)").contains(message));
+    assert!(eval_err("(if #t (define x 1) 2)").contains(message));
+    //Only a misplaced define form gets the message, not any mention of define.
+    assert!(eval_err("(list define)").contains("define is not defined."));
+    assert!(eval_err("(list include)").contains("include is not defined."));
+    assert_eq!(
+        eval("(let ((define (lambda (x y) (+ x y)))) (+ 1 (define 2 3)))").unwrap(),
+        SchemeType::Number(6)
+    );
+}
+
+#[test]
+fn hash_table_update() {
+    let prog = "
+        (define h (make-hash-table))
+        (define (inc x) (+ x 1))
+        (map (lambda (word) (hash-table-update! h word inc (lambda () 0))) '(a b a c a))
+        (hash-table-set! h 'd 10)
+        (hash-table-update! h 'd inc)
+        (hash-table-update!/default h 'e inc 100)
+        (and (= (hash-table-ref h 'a) 3)
+            (= (hash-table-ref h 'b) 1)
+            (= (hash-table-ref h 'd) 11)
+            (= (hash-table-ref h 'e) 101)
+            (= (hash-table-count h) 5))";
+    assert!(eval_is_true(prog));
+
+    assert!(eval("(hash-table-update! (make-hash-table) 'a (lambda (x) x))").is_err());
+}
+
+#[test]
+fn alist_to_hash_table() {
+    let prog = "
+        (define h (alist->hash-table '((a . 1) (b . 2) (a . 3))))
+        (and (= (hash-table-count h) 2)
+            (= (hash-table-ref h 'a) 1)
+            (= (hash-table-ref h 'a) (cdr (assoc 'a '((a . 1) (b . 2) (a . 3)))))
+            (= (hash-table-ref h 'b) 2))";
+    assert!(eval_is_true(prog));
+
+    let prog = "
+        (define h (make-hash-table))
+        (define copy #f)
+        (dotimes (i 20) (hash-table-set! h (list i) (* i 2)))
+        (set! copy (alist->hash-table (hash-table->alist h)))
+        (and (= (hash-table-count copy) 20)
+            (= (hash-table-ref copy '(7)) 14)
+            (= (apply + (map car (hash-table-keys copy))) 190))";
+    assert!(eval_is_true(prog));
+
+    assert!(eval_is_true(
+        "(not (hash-table-contains? (alist->hash-table '((\"a\" . 1)) eqv?) (list->string (list #\\a))))"
+    ));
+}
+
+#[test]
+fn default_fill() {
+    assert!(eval_is_true(
+        "(equal? (make-vector 3) (vector (if #f #f) (if #f #f) (if #f #f)))"
+    ));
+    assert!(eval_is_true("(equal? (make-vector 2) (vector #f #f))"));
+    assert!(eval_is_true("(equal? (make-vector 2 'x) (vector 'x 'x))"));
+    assert!(eval_is_true("(equal? (make-string 3) \"   \")"));
+    assert!(eval_is_true("(equal? (make-string 2 #\\z) \"zz\")"));
+    assert!(eval_is_true("(equal? (make-string 0) \"\")"));
+}
+
+#[test]
+fn without_prelude() {
+    assert!(eval_without_prelude("list").is_err());
+    assert!(eval_without_prelude("(map car '((1)))").is_err());
+    assert_eq!(
+        eval_without_prelude("(+ 1 2)").unwrap(),
+        SchemeType::Number(3)
+    );
+    assert_eq!(
+        eval_without_prelude("(car (cdr (cons 1 (cons 2 '()))))").unwrap(),
+        SchemeType::Number(2)
+    );
+    assert_eq!(
+        eval_without_prelude("(define (list . x) (cons 'mine x)) (car (list 1 2))").unwrap(),
+        eval("'mine").unwrap()
+    );
+    assert!(eval("list").is_ok());
+}
+
+#[test]
+fn include() {
+    let dir = env::temp_dir().join(format!("scheme-oxide-include-{}", std::process::id()));
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(
+        dir.join("lib").join("outer.scm"),
+        "(include \"inner.scm\") (define (double-inc x) (* (inc x) 2))",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("lib").join("inner.scm"),
+        "(define (inc x) (+ x 1))",
+    )
+    .unwrap();
+    fs::write(dir.join("self.scm"), "(include \"self.scm\")").unwrap();
+
+    let result = with_include_dir(&dir, || eval("(include \"lib/outer.scm\") (double-inc 3)"));
+    assert_eq!(result.unwrap(), SchemeType::Number(8));
+
+    let absolute = format!(
+        "(define (f) (include \"{}\") (inc 1)) (f)",
+        dir.join("lib").join("inner.scm").display()
+    );
+    assert_eq!(eval(&absolute).unwrap(), SchemeType::Number(2));
+
+    let result = with_include_dir(&dir, || eval("(include \"lib/outer.scm\")"));
+    assert!(result.is_ok());
+
+    let missing = with_include_dir(&dir, || eval("(include \"missing.scm\") 1"));
+    assert!(format!("{:?}", missing.unwrap_err()).contains("Could not include"));
+    assert!(with_include_dir(&dir, || eval("(include \"self.scm\")")).is_err());
+    assert!(eval_err("(+ 1 (include \"x.scm\"))").contains("include can only be used"));
+    assert!(eval_err("(include 5)").contains("include needs a string"));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn empty_combination() {
+    assert!(eval_err("()").contains("Tried to call the empty list"));
+    assert!(eval_err("(+ 1 ())").contains("Tried to call the empty list"));
+    assert!(eval_err("(lambda () ())").contains("Tried to call the empty list"));
+    assert!(eval_is_true("(null? '())"));
+    assert!(eval_is_true("(null? (quote ()))"));
+    assert!(eval_is_true("(eq? '() (cdr (list 1)))"));
+}
+
+#[test]
+fn uninterned_symbols() {
+    assert!(eval_is_true("(symbol-interned? 'foo)"));
+    assert!(eval_is_true("(symbol-interned? (symbol-append 'a 'b))"));
+    assert!(eval_is_true("(not (symbol-interned? (gensym)))"));
+    assert!(eval_is_true("(symbol? (gensym))"));
+    assert!(eval_is_true("(not (eqv? (gensym) (gensym)))"));
+    assert!(eval_is_true(
+        "(let ((g (gensym))) (and (eqv? g g) (not (eqv? g (symbol-append g)))))"
+    ));
+    assert!(eval("(symbol-interned? \"foo\")").is_err());
+}
+
+#[test]
+fn write_chars() {
+    let written = |datum: &str| {
+        eval_to_string(&format!(
+            "(define port (open-output-string))
+            (write {} port)
+            (get-output-string port)",
+            datum
+        ))
+    };
+    let displayed = |datum: &str| {
+        eval_to_string(&format!(
+            "(define port (open-output-string))
+            (display {} port)
+            (get-output-string port)",
+            datum
+        ))
+    };
+
+    assert_eq!(written("#\\space"), "#\\space");
+    assert_eq!(written("#\\A"), "#\\A");
+    assert_eq!(written("#\\newline"), "#\\newline");
+    assert_eq!(written("#\\x7f"), "#\\delete");
+    assert_eq!(written("#\\x0"), "#\\null");
+    assert_eq!(written("#\\x1"), "#\\x1");
+    assert_eq!(written("#\\λ"), "#\\λ");
+    assert_eq!(written("'(#\\a \"b\\\"c\")"), "(#\\a \"b\\\"c\")");
+    assert_eq!(displayed("#\\space"), " ");
+    assert_eq!(displayed("#\\A"), "A");
+    assert_eq!(displayed("'(#\\a \"b\\\"c\")"), "(a b\"c)");
+
+    assert_eq!(
+        eval("(char->integer #\\A)").unwrap(),
+        SchemeType::Number(65)
+    );
+    assert_eq!(eval("(integer->char 955)").unwrap(), SchemeType::Char('λ'));
+    assert!(eval("(integer->char 55296)").is_err());
+    assert!(eval("(integer->char -1)").is_err());
+}
+
+#[test]
+fn integer_division() {
+    let cases = [
+        (7, 2, 3, 1, 3, 1),
+        (-7, 2, -3, -1, -4, 1),
+        (7, -2, -3, 1, -4, -1),
+        (-7, -2, 3, -1, 3, -1),
+        (6, 3, 2, 0, 2, 0),
+        (-6, 3, -2, 0, -2, 0),
+        (0, -5, 0, 0, 0, 0),
+    ];
+    for &(n, d, tq, tr, fq, fr) in &cases {
+        let call = |name: &str| eval(&format!("({} {} {})", name, n, d)).unwrap();
+        assert_eq!(call("truncate-quotient"), SchemeType::Number(tq));
+        assert_eq!(call("quotient"), SchemeType::Number(tq));
+        assert_eq!(call("truncate-remainder"), SchemeType::Number(tr));
+        assert_eq!(call("remainder"), SchemeType::Number(tr));
+        assert_eq!(call("floor-quotient"), SchemeType::Number(fq));
+        assert_eq!(call("floor-remainder"), SchemeType::Number(fr));
+        assert_eq!(call("modulo"), SchemeType::Number(fr));
+    }
+    assert!(eval("(floor-quotient 1 0)").is_err());
+    assert!(eval("(floor-remainder 1 0)").is_err());
+}
+
+#[test]
+fn any_every() {
+    assert!(eval_is_true("(any even? (list 1 3 4))"));
+    assert!(eval_is_true("(not (any even? (list 1 3 5)))"));
+    assert!(eval_is_true("(not (any even? '()))"));
+    assert!(eval_is_true("(every odd? (list 1 3 5))"));
+    assert!(eval_is_true("(not (every odd? (list 1 2 5)))"));
+    assert!(eval_is_true("(every odd? '())"));
+    assert_eq!(
+        eval("(any (lambda (x) (and (> x 1) (* x 10))) '(1 2 3))").unwrap(),
+        SchemeType::Number(20)
+    );
+    assert_eq!(
+        eval("(every (lambda (x) (* x 10)) '(1 2 3))").unwrap(),
+        SchemeType::Number(30)
+    );
+    assert!(eval_is_true("(any < '(3 2 1) '(1 1 2 5))"));
+    assert!(eval_is_true("(every < '(1 2) '(2 3 0))"));
+
+    let prog = "
+        (define calls 0)
+        (define (counted pred) (lambda (x) (set! calls (+ calls 1)) (pred x)))
+        (and (any (counted even?) '(1 2 3 4))
+            (= calls 2)
+            (begin (set! calls 0) (not (every (counted odd?) '(1 2 3 5))))
+            (= calls 2))";
+    assert!(eval_is_true(prog));
+}
+
+#[test]
+fn improper_formals() {
+    assert!(eval_is_true(
+        "(equal? ((lambda (a b . rest) (list a b rest)) 1 2 3 4) '(1 2 (3 4)))"
+    ));
+    assert!(eval_is_true("(null? ((lambda (a b . rest) rest) 1 2))"));
+    assert!(eval("((lambda (a b . rest) rest) 1)").is_err());
+    assert!(eval_is_true(
+        "(equal? (procedure-arity (lambda (a b . rest) a)) '(2 . #f))"
+    ));
+    assert!(eval_is_true(
+        "(let* ((args (list 3 4))
+                (rest (apply (lambda (a . rest) rest) 1 args)))
+            (set-car! rest 5)
+            (and (not (eq? rest (cdr args))) (equal? args '(3 4)) (equal? rest '(5 4))))"
+    ));
+    assert!(matches!(
+        eval("((lambda (a . b . c) a) 1)"),
+        Err(RuntimeError::ReadError(_))
+    ));
+    assert!(eval_err("(lambda (a . 1) a)").contains("needs a symbol got a number"));
+    assert!(eval_err("(lambda (a 1 . c) a)").contains("needs a symbol got a number"));
+}
+
+#[test]
+fn sort_strings() {
+    assert!(eval_is_true(
+        "(equal? (sort string<? (list \"banana\" \"apple\")) '(\"apple\" \"banana\"))"
+    ));
+    assert!(eval_is_true(
+        "(equal? (sort string<? (list \"b\" \"B\" \"a\" \"A\")) '(\"A\" \"B\" \"a\" \"b\"))"
+    ));
+    assert!(eval_is_true(
+        "(equal? (sort string-ci<? (list \"b\" \"B\" \"a\" \"A\")) '(\"a\" \"A\" \"b\" \"B\"))"
+    ));
+    assert!(eval_is_true("(equal? (sort < '(3 1 2 1)) '(1 1 2 3))"));
+    assert!(eval_is_true("(null? (sort < '()))"));
+    assert!(eval_is_true(
+        "(equal? (sort (lambda (x y) (< (car x) (car y))) '((2 a) (1 b) (2 c) (1 d))) '((1 b) (1 d) (2 a) (2 c)))"
+    ));
+    assert!(eval_is_true(
+        "(let* ((lst (list 1)) (sorted (sort < lst))) (and (equal? sorted lst) (not (eq? sorted lst))))"
+    ));
+
+    assert!(eval_is_true("(string<? \"ab\" \"abc\" \"b\")"));
+    assert!(eval_is_true("(not (string<? \"abc\" \"ab\"))"));
+    assert!(eval_is_true("(string=? \"abc\" \"abc\")"));
+    assert!(eval_is_true("(string>=? \"b\" \"b\" \"a\")"));
+    assert!(eval_is_true("(string-ci=? \"Hello\" \"hELLO\")"));
+    assert!(eval_is_true("(not (string-ci<? \"B\" \"a\"))"));
+}
+
+#[test]
+fn vector_sort_search() {
+    assert!(eval_is_true(
+        "(let ((v (vector 3 1 2 5 4))) (vector-sort! v <) (equal? v #(1 2 3 4 5)))"
+    ));
+    assert!(eval_is_true(
+        "(let ((v (vector '(2 a) '(1 b) '(2 c) '(1 d))))
+            (vector-sort! v (lambda (x y) (< (car x) (car y))))
+            (equal? v #((1 b) (1 d) (2 a) (2 c))))"
+    ));
+    assert!(eval_is_true(
+        "(let ((v (vector))) (vector-sort! v <) (equal? v #()))"
+    ));
+
+    assert_eq!(
+        eval("(vector-binary-search #(1 3 5 7) 5 -)").unwrap(),
+        SchemeType::Number(2)
+    );
+    assert_eq!(
+        eval("(vector-binary-search #(1 3 5 7) 1 -)").unwrap(),
+        SchemeType::Number(0)
+    );
+    assert_eq!(
+        eval("(vector-binary-search #(1 3 5 7) 7 -)").unwrap(),
+        SchemeType::Number(3)
+    );
+    assert!(eval_is_true("(not (vector-binary-search #(1 3 5 7) 4 -))"));
+    assert!(eval_is_true("(not (vector-binary-search #(1 3 5 7) 8 -))"));
+    assert!(eval_is_true("(not (vector-binary-search #() 1 -))"));
+}
+
+#[test]
+fn call_non_procedure() {
+    let not_a_procedure = |prog| match eval(prog) {
+        Err(RuntimeError::NotAProcedure(type_name)) => type_name,
+        result => panic!("{:?}", result),
+    };
+
+    assert_eq!(not_a_procedure("(5 6 7)"), "number");
+    assert_eq!(not_a_procedure("(\"abc\" 1)"), "string");
+    assert_eq!(not_a_procedure("('() 1)"), "empty list");
+    assert_eq!(not_a_procedure("((quote x) 1)"), "symbol");
+    assert_eq!(not_a_procedure("(apply '(1) '())"), "pair");
+    assert_eq!(
+        RuntimeError::NotAProcedure("number").to_string(),
+        "Cannot call a number, which is not a procedure."
+    );
+}
+
+#[test]
+fn and_or_map() {
+    assert!(eval_is_true("(eq? (and-map positive? (list 1 2 3)) #t)"));
+    assert!(eval_is_true(
+        "(eq? (and-map (lambda (x) (* x 10)) '(1 2)) #t)"
+    ));
+    assert!(eval_is_true("(eq? (and-map positive? '(1 -2 3)) #f)"));
+    assert!(eval_is_true("(eq? (and-map positive? '()) #t)"));
+    assert!(eval_is_true(
+        "(eq? (or-map (lambda (x) (and (> x 1) x)) '(1 2)) #t)"
+    ));
+    assert!(eval_is_true("(eq? (or-map positive? '(-1 -2)) #f)"));
+    assert!(eval_is_true("(eq? (or-map < '(3 1) '(2 2)) #t)"));
+
+    let prog = "
+        (define calls 0)
+        (define (counted pred) (lambda (x) (set! calls (+ calls 1)) (pred x)))
+        (and (or-map (counted even?) '(1 2 3 4))
+            (= calls 2)
+            (begin (set! calls 0) (not (and-map (counted odd?) '(1 2 3 5))))
+            (= calls 2))";
+    assert!(eval_is_true(prog));
+}
+
+#[test]
+fn list_values() {
+    assert!(eval_is_true(
+        "(equal? (values->list (values 1 2 3)) '(1 2 3))"
+    ));
+    assert!(eval_is_true("(equal? (values->list 1) '(1))"));
+    assert!(eval_is_true("(null? (values->list (values)))"));
+    assert!(eval_is_true("(null? (values->list (list->values '())))"));
+    assert!(eval_is_true(
+        "(equal? (values->list (list->values (list 1 '(2) 3))) '(1 (2) 3))"
+    ));
+    assert_eq!(
+        eval("(call-with-values (lambda () (list->values (list 4 5))) +)").unwrap(),
+        SchemeType::Number(9)
+    );
+    assert!(eval_is_true(
+        "(call-with-values (lambda () (values)) (lambda args (null? args)))"
+    ));
+    assert_eq!(eval("(list->values '(7))").unwrap(), SchemeType::Number(7));
+}
+
+#[test]
+fn identity_const() {
+    assert_eq!(eval("(identity 5)").unwrap(), SchemeType::Number(5));
+    assert!(eval_is_true("(equal? (identity \"abc\") \"abc\")"));
+    assert!(eval_is_true("(eq? (identity 'a) 'a)"));
+    assert!(eval_is_true("(let ((x (list 1 2))) (eq? (identity x) x))"));
+    assert!(eval_is_true("(not (identity #f))"));
+    assert!(eval("(identity)").is_err());
+    assert!(eval("(identity 1 2)").is_err());
+
+    assert_eq!(eval("((const 7) 1 2 3)").unwrap(), SchemeType::Number(7));
+    assert_eq!(eval("((const 7))").unwrap(), SchemeType::Number(7));
+    assert_eq!(eval("((const 7) 'a)").unwrap(), SchemeType::Number(7));
+    assert!(eval_is_true("(equal? (map (const 0) '(1 2 3)) '(0 0 0))"));
+}
+
+#[test]
+fn length_plus() {
+    assert_eq!(
+        eval("(length+ (list 1 2 3))").unwrap(),
+        SchemeType::Number(3)
+    );
+    assert_eq!(
+        eval("(length+ (list 1 2 3 4))").unwrap(),
+        SchemeType::Number(4)
+    );
+    assert_eq!(eval("(length+ '())").unwrap(), SchemeType::Number(0));
+    assert_eq!(eval("(length+ '(1 2 . 3))").unwrap(), SchemeType::Number(2));
+    assert_eq!(
+        eval("(length (list 1 2 3))").unwrap(),
+        SchemeType::Number(3)
+    );
+    assert!(eval("(length '(1 2 . 3))").is_err());
+
+    for size in 1..5 {
+        let cycle = format!(
+            "(define cyclic (list 1 2 3 4))
+            (set-cdr! (list-tail cyclic 3) (list-tail cyclic {}))",
+            4 - size
+        );
+        assert!(eval_is_true(&format!("{} (not (length+ cyclic))", cycle)));
+        assert!(eval(&format!("{} (length cyclic)", cycle)).is_err());
+    }
+}
@@ -19,60 +19,123 @@
 
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
 
 use crate::environment;
 use crate::interpreter::FunctionRef;
 
-pub use self::object::SchemeObject;
+pub use self::bytevector::SchemeBytevector;
+pub use self::object::{SchemeObject, WeakSchemeObject};
 pub use self::string::SchemeString;
 pub use self::string::StringSetError;
 
+mod bytevector;
 mod object;
 mod string;
 
-pub fn new_symbol(name: String) -> SchemeObject {
-    thread_local! {
-        static NAME_TO_SYM_MAP: RefCell<HashMap<String, SchemeObject>> = RefCell::new(HashMap::new())
+#[cfg(test)]
+mod test;
+
+//Size the symbol table starts pruning at.
+const MIN_SYMBOL_PRUNE_LEN: usize = 64;
+
+//Symbols are held weakly, so ones a program no longer uses are freed and their names can be
+//interned again as new symbols. Symbols still in use keep their identity.
+struct SymbolTable {
+    symbols: HashMap<String, WeakSchemeObject>,
+    //Pruning waits until the table doubles in size so interning stays amortized constant time.
+    prune_len: usize,
+}
+
+impl SymbolTable {
+    fn new() -> Self {
+        SymbolTable {
+            symbols: HashMap::new(),
+            prune_len: MIN_SYMBOL_PRUNE_LEN,
+        }
     }
 
-    NAME_TO_SYM_MAP.with(|raw_sym_map| {
-        let mut sym_map = raw_sym_map.borrow_mut();
+    fn intern(&mut self, name: String) -> SchemeObject {
+        if let Some(symbol) = self.symbols.get(&name).and_then(WeakSchemeObject::upgrade) {
+            return symbol;
+        }
 
-        sym_map
-            .entry(name.clone())
-            .or_insert_with(|| {
-                SchemeObject::new(
-                    environment::symbol_type_id(),
-                    vec![SchemeType::String(name.parse().unwrap())],
-                )
-            })
-            .clone()
+        if self.symbols.len() >= self.prune_len {
+            self.prune();
+        }
+
+        let symbol = new_uninterned_symbol(name.clone());
+        self.symbols.insert(name, symbol.downgrade());
+        symbol
+    }
+
+    //Drops the names of symbols that have been freed.
+    fn prune(&mut self) {
+        self.symbols.retain(|_, symbol| symbol.is_alive());
+        self.prune_len = MIN_SYMBOL_PRUNE_LEN.max(self.symbols.len() * 2);
+    }
+}
+
+thread_local! {
+    static SYMBOL_TABLE: RefCell<SymbolTable> = RefCell::new(SymbolTable::new());
+}
+
+pub fn new_symbol(name: String) -> SchemeObject {
+    SYMBOL_TABLE.with(|table| table.borrow_mut().intern(name))
+}
+
+//Never eqv? to any other symbol, even one with the same name.
+pub fn new_uninterned_symbol(name: String) -> SchemeObject {
+    SchemeObject::new(
+        environment::symbol_type_id(),
+        vec![SchemeType::String(name.as_str().into())],
+    )
+}
+
+//Whether symbol is the one new_symbol gives for its name.
+pub fn is_interned(symbol: &SchemeObject) -> bool {
+    let name = symbol
+        .get_field(0)
+        .unwrap()
+        .into_string()
+        .unwrap()
+        .to_string();
+
+    SYMBOL_TABLE.with(|table| {
+        table
+            .borrow()
+            .symbols
+            .get(&name)
+            .and_then(WeakSchemeObject::upgrade)
+            .as_ref()
+            == Some(symbol)
     })
 }
 
+//Builds lists directly out of pairs, without calling into $make-list-factory for each element.
 #[derive(Clone, Debug)]
 pub struct ListFactory {
-    push_fn: FunctionRef,
-    res_fn: FunctionRef,
+    pair_type_id: SchemeType,
+    elements: Vec<SchemeType>,
 }
 
 impl ListFactory {
     pub fn new(mutable: bool) -> Self {
-        let list_factory = environment::make_list_factory(mutable.into()).unwrap();
-        let push_fn = environment::car(list_factory.clone())
-            .unwrap()
-            .to_function()
-            .unwrap();
-        let res_fn = environment::cdr(list_factory)
-            .unwrap()
-            .to_function()
-            .unwrap();
+        let pair_type_id = if mutable {
+            environment::mutable_pair_type_id()
+        } else {
+            environment::immutable_pair_type_id()
+        };
 
-        Self { push_fn, res_fn }
+        Self {
+            pair_type_id,
+            elements: Vec::new(),
+        }
     }
 
     pub fn push(&mut self, object: SchemeType) {
-        self.push_fn.clone().call(vec![object]).unwrap();
+        self.elements.push(object)
     }
 
     pub fn build(self) -> SchemeType {
@@ -80,16 +143,25 @@ impl ListFactory {
     }
 
     pub fn build_with_tail(self, object: SchemeType) -> SchemeType {
-        self.res_fn.call(vec![object]).unwrap()
+        let pair_type_id = self.pair_type_id;
+
+        self.elements
+            .into_iter()
+            .rev()
+            .fold(object, |tail, element| {
+                SchemeObject::new(pair_type_id.clone(), vec![element, tail]).into()
+            })
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+//The derived Hash agrees with eqv?.
+#[derive(Clone, PartialEq, Hash, Debug)]
 pub enum SchemeType {
     Function(FunctionRef),
     Number(i64),
     Char(char),
     String(SchemeString),
+    Bytevector(SchemeBytevector),
     Object(SchemeObject),
 }
 
@@ -105,21 +177,18 @@ impl SchemeType {
         }
     }
 
+    //Fails if the number does not fit in T.
+    pub fn to_ranged<T: TryFrom<i64>>(&self) -> Result<T, CastError> {
+        T::try_from(self.to_number()?).map_err(|_| CastError)
+    }
+
+    //Indexes need to be positive and fit in usize even on 32-bit platforms.
     pub fn to_index(&self) -> Result<usize, CastError> {
-        let raw_num = self.to_number()?;
-        //Indexes need to be positive
-        if raw_num < 0 {
-            return Err(CastError);
-        }
-        let num = raw_num as u64;
+        self.to_ranged()
+    }
 
-        //On 32-bit platforms make sure that the index does not overflow.
-        //Should be optimized to a no-op on 64-bit platforms.
-        if num > (usize::max_value() as u64) {
-            Err(CastError)
-        } else {
-            Ok(num as usize)
-        }
+    pub fn to_u8(&self) -> Result<u8, CastError> {
+        self.to_ranged()
     }
 
     pub fn to_char(&self) -> Result<char, CastError> {
@@ -130,6 +199,14 @@ impl SchemeType {
         }
     }
 
+    pub fn into_bytevector(self) -> Result<SchemeBytevector, CastError> {
+        if let SchemeType::Bytevector(bytes) = self {
+            Ok(bytes)
+        } else {
+            Err(CastError)
+        }
+    }
+
     pub fn into_object(self) -> Result<SchemeObject, CastError> {
         if let SchemeType::Object(obj) = self {
             Ok(obj)
@@ -146,10 +223,99 @@ impl SchemeType {
         }
     }
 
+    pub fn is_pair(&self) -> bool {
+        if let SchemeType::Object(obj) = self {
+            let type_id = obj.get_type_id();
+            type_id == environment::mutable_pair_type_id()
+                || type_id == environment::immutable_pair_type_id()
+        } else {
+            false
+        }
+    }
+
+    pub fn is_vector(&self) -> bool {
+        if let SchemeType::Object(obj) = self {
+            obj.get_type_id() == environment::vector_type_id()
+        } else {
+            false
+        }
+    }
+
+    //Converts a proper list into a vector of its elements.
+    pub fn into_vec(self) -> Result<Vec<SchemeType>, CastError> {
+        let mut ret = Vec::new();
+        let mut list = self;
+
+        while list != environment::empty_list() {
+            if !list.is_pair() {
+                return Err(CastError);
+            }
+
+            let pair = list.into_object()?;
+            ret.push(pair.get_field(0).unwrap());
+            list = pair.get_field(1).unwrap();
+        }
+
+        Ok(ret)
+    }
+
+    //Hashes the same way for any two objects that are equal?.
+    pub fn equal_hash<H: Hasher>(&self, state: &mut H) {
+        let mut object = self.clone();
+
+        //Loop down the cdr of lists to avoid recursing on long lists.
+        while object.is_pair() {
+            let pair = object.into_object().unwrap();
+            pair.get_field(0).unwrap().equal_hash(state);
+            object = pair.get_field(1).unwrap();
+        }
+
+        if object.is_vector() {
+            let vector = object.into_object().unwrap();
+            for index in 0..vector.field_count() {
+                vector.get_field(index).unwrap().equal_hash(state);
+            }
+            return;
+        }
+
+        match object {
+            SchemeType::String(stri) => stri.to_string().hash(state),
+            SchemeType::Bytevector(bytes) => bytes.hash_contents(state),
+            _ => object.hash(state),
+        }
+    }
+
     pub fn to_bool(&self) -> bool {
         *self != environment::s_false()
     }
 
+    //Used to describe the value in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            SchemeType::Function(_) => "procedure",
+            SchemeType::Number(_) => "number",
+            SchemeType::Char(_) => "character",
+            SchemeType::String(_) => "string",
+            SchemeType::Bytevector(_) => "bytevector",
+            SchemeType::Object(obj) => {
+                let type_id = obj.get_type_id();
+                if self.is_pair() {
+                    "pair"
+                } else if self.is_vector() {
+                    "vector"
+                } else if type_id == environment::symbol_type_id() {
+                    "symbol"
+                } else if *self == environment::empty_list() {
+                    "empty list"
+                } else if *self == environment::s_true() || *self == environment::s_false() {
+                    "boolean"
+                } else {
+                    "object"
+                }
+            }
+        }
+    }
+
     pub fn to_function(&self) -> Result<FunctionRef, CastError> {
         Ok(match self {
             SchemeType::Function(func) => func.clone(),
@@ -176,6 +342,12 @@ impl From<SchemeString> for SchemeType {
     }
 }
 
+impl From<SchemeBytevector> for SchemeType {
+    fn from(bytes: SchemeBytevector) -> Self {
+        SchemeType::Bytevector(bytes)
+    }
+}
+
 impl From<bool> for SchemeType {
     fn from(is_true: bool) -> Self {
         if is_true {
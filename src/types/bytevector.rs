@@ -0,0 +1,81 @@
+/*
+    Copyright 2019 Alexander Eckhart
+
+    This file is part of scheme-oxide.
+
+    Scheme-oxide is free software: you can redistribute it and/or modify
+    it under the terms of the GNU General Public License as published by
+    the Free Software Foundation, either version 3 of the License, or
+    (at your option) any later version.
+
+    Scheme-oxide is distributed in the hope that it will be useful,
+    but WITHOUT ANY WARRANTY; without even the implied warranty of
+    MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+    GNU General Public License for more details.
+
+    You should have received a copy of the GNU General Public License
+    along with scheme-oxide.  If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::cell::Cell;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+#[derive(Clone, Debug)]
+pub struct SchemeBytevector(Rc<SchemeBytevectorInner>);
+
+#[derive(Debug)]
+struct SchemeBytevectorInner {
+    mutable: bool,
+    bytes: Box<[Cell<u8>]>,
+}
+
+impl SchemeBytevector {
+    pub fn new(bytes: Vec<u8>, mutable: bool) -> SchemeBytevector {
+        SchemeBytevector(Rc::new(SchemeBytevectorInner {
+            mutable,
+            bytes: bytes.into_iter().map(Cell::new).collect(),
+        }))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.bytes.len()
+    }
+
+    pub fn is_mutable(&self) -> bool {
+        self.0.mutable
+    }
+
+    pub fn get(&self, index: usize) -> Option<u8> {
+        self.0.bytes.get(index).map(Cell::get)
+    }
+
+    //Error if index is out of bounds.
+    //Mutability is left for the caller to check.
+    pub fn set(&self, index: usize, byte: u8) -> Result<(), ()> {
+        self.0
+            .bytes
+            .get(index)
+            .map(|byte_ptr| byte_ptr.set(byte))
+            .ok_or(())
+    }
+
+    //Unlike Hash, which goes by identity like eqv?.
+    pub fn hash_contents<H: Hasher>(&self, state: &mut H) {
+        for byte in self.0.bytes.iter() {
+            byte.get().hash(state)
+        }
+    }
+}
+
+impl PartialEq for SchemeBytevector {
+    fn eq(&self, other: &SchemeBytevector) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Hash for SchemeBytevector {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).hash(state)
+    }
+}
@@ -19,11 +19,16 @@
 
 use crate::types::*;
 use std::cell::RefCell;
-use std::rc::Rc;
+use std::hash::{Hash, Hasher};
+use std::rc::{Rc, Weak};
 
 #[derive(Clone, Debug)]
 pub struct SchemeObject(Rc<SchemeObjectInner>);
 
+//Refers to an object without keeping it alive.
+#[derive(Clone, Debug)]
+pub struct WeakSchemeObject(Weak<SchemeObjectInner>);
+
 #[derive(Debug)]
 struct SchemeObjectInner {
     type_id: SchemeType,
@@ -44,10 +49,18 @@ impl SchemeObject {
         SchemeObject::new(SchemeType::Number(0), Vec::new())
     }
 
+    pub fn downgrade(&self) -> WeakSchemeObject {
+        WeakSchemeObject(Rc::downgrade(&self.0))
+    }
+
     pub fn get_type_id(&self) -> SchemeType {
         self.0.type_id.clone()
     }
 
+    pub fn field_count(&self) -> usize {
+        self.0.fields.borrow().len()
+    }
+
     pub fn get_field(&self, index: usize) -> Option<SchemeType> {
         self.0.fields.borrow().get(index).cloned()
     }
@@ -63,8 +76,25 @@ impl SchemeObject {
     }
 }
 
+impl WeakSchemeObject {
+    //None once the object has been freed.
+    pub fn upgrade(&self) -> Option<SchemeObject> {
+        self.0.upgrade().map(SchemeObject)
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.0.strong_count() != 0
+    }
+}
+
 impl PartialEq for SchemeObject {
     fn eq(&self, other: &SchemeObject) -> bool {
         Rc::ptr_eq(&self.0, &other.0)
     }
 }
+
+impl Hash for SchemeObject {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).hash(state)
+    }
+}
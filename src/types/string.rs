@@ -19,6 +19,8 @@
 
 use std::cell::Cell;
 use std::convert::Infallible;
+use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::rc::Rc;
 use std::str;
 
@@ -82,11 +84,23 @@ impl PartialEq for SchemeString {
     }
 }
 
-impl str::FromStr for SchemeString {
-    //Will be the never type in future rust versions.
-    type Err = Infallible;
+impl Hash for SchemeString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).hash(state)
+    }
+}
 
-    fn from_str(s: &str) -> Result<Self, Infallible> {
+impl fmt::Display for SchemeString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars.iter() {
+            write!(f, "{}", c.get())?;
+        }
+        Ok(())
+    }
+}
+
+impl From<&str> for SchemeString {
+    fn from(s: &str) -> Self {
         thread_local! {
             static EMPTY_STRING: SchemeString = SchemeString(Rc::new(SchemeStringInner {
                 mutable: false,
@@ -95,7 +109,7 @@ impl str::FromStr for SchemeString {
         }
 
         if s == "" {
-            return Ok(EMPTY_STRING.with(Clone::clone));
+            return EMPTY_STRING.with(Clone::clone);
         }
 
         let mut chars = Vec::new();
@@ -103,9 +117,18 @@ impl str::FromStr for SchemeString {
         for c in s.chars() {
             chars.push(Cell::new(c))
         }
-        Ok(SchemeString(Rc::new(SchemeStringInner {
+        SchemeString(Rc::new(SchemeStringInner {
             mutable: false,
             chars: chars.into_boxed_slice(),
-        })))
+        }))
+    }
+}
+
+impl str::FromStr for SchemeString {
+    //Will be the never type in future rust versions.
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Infallible> {
+        Ok(s.into())
     }
 }
@@ -0,0 +1,110 @@
+use super::*;
+
+#[test]
+fn to_u8() {
+    assert_eq!(SchemeType::Number(0).to_u8().unwrap(), 0);
+    assert_eq!(SchemeType::Number(255).to_u8().unwrap(), 255);
+    assert!(SchemeType::Number(256).to_u8().is_err());
+    assert!(SchemeType::Number(-1).to_u8().is_err());
+    assert!(SchemeType::Char('a').to_u8().is_err());
+}
+
+#[test]
+fn to_index() {
+    assert_eq!(SchemeType::Number(0).to_index().unwrap(), 0);
+    assert_eq!(SchemeType::Number(1 << 20).to_index().unwrap(), 1 << 20);
+    assert!(SchemeType::Number(-1).to_index().is_err());
+}
+
+#[test]
+fn to_ranged() {
+    assert_eq!(SchemeType::Number(-5).to_ranged::<i32>().unwrap(), -5);
+    assert_eq!(
+        SchemeType::Number(i64::from(i32::MAX))
+            .to_ranged::<i32>()
+            .unwrap(),
+        i32::MAX
+    );
+    assert!(SchemeType::Number(i64::from(i32::MAX) + 1)
+        .to_ranged::<i32>()
+        .is_err());
+    assert!(SchemeType::Number(i64::from(i32::MIN) - 1)
+        .to_ranged::<i32>()
+        .is_err());
+    assert_eq!(
+        SchemeType::Number(i64::MIN).to_ranged::<i64>().unwrap(),
+        i64::MIN
+    );
+    assert!(SchemeType::Number(70000).to_ranged::<u16>().is_err());
+    assert!(environment::s_false().to_ranged::<i32>().is_err());
+}
+
+#[test]
+fn list_factory() {
+    let count = 20000;
+    let scheme_built = crate::interpreter::eval(&format!(
+        "(let ((factory ($make-list-factory #t)))
+            (dotimes (i {}) ((car factory) i))
+            ((cdr factory) 'tail))",
+        count
+    ))
+    .unwrap();
+
+    let mut factory = ListFactory::new(true);
+    for i in 0..count {
+        factory.push(SchemeType::Number(i));
+    }
+    let tail = crate::interpreter::eval("'tail").unwrap();
+    let native_built = factory.build_with_tail(tail.clone());
+
+    let mut scheme_list = scheme_built;
+    let mut native_list = native_built;
+    while scheme_list.is_pair() {
+        let scheme_pair = scheme_list.into_object().unwrap();
+        let native_pair = native_list.into_object().unwrap();
+        assert_eq!(scheme_pair.get_type_id(), native_pair.get_type_id());
+        assert_eq!(scheme_pair.get_field(0), native_pair.get_field(0));
+        scheme_list = scheme_pair.get_field(1).unwrap();
+        native_list = native_pair.get_field(1).unwrap();
+    }
+    assert_eq!(scheme_list, tail);
+    assert_eq!(native_list, tail);
+
+    let immutable = ListFactory::new(false);
+    assert_eq!(immutable.build(), environment::empty_list());
+    let mut immutable = ListFactory::new(false);
+    immutable.push(SchemeType::Number(1));
+    assert_eq!(
+        immutable.build().into_object().unwrap().get_type_id(),
+        environment::immutable_pair_type_id()
+    );
+}
+
+#[test]
+fn symbol_table_prune() {
+    let symbol_count = || SYMBOL_TABLE.with(|table| table.borrow().symbols.len());
+
+    let kept = new_symbol("kept".to_string());
+    let transient = (0..1000)
+        .map(|index| new_symbol(format!("transient{}", index)))
+        .collect::<Vec<_>>();
+    assert!(symbol_count() > 1000);
+    assert_eq!(new_symbol("transient0".to_string()), transient[0]);
+
+    drop(transient);
+    SYMBOL_TABLE.with(|table| table.borrow_mut().prune());
+    assert!(symbol_count() < 1000);
+    assert!(SYMBOL_TABLE.with(|table| !table.borrow().symbols.contains_key("transient1")));
+
+    assert_eq!(new_symbol("kept".to_string()), kept);
+    assert!(is_interned(&kept));
+    let interned_again = new_symbol("transient0".to_string());
+    assert!(is_interned(&interned_again));
+    assert_eq!(new_symbol("transient0".to_string()), interned_again);
+
+    //Interning prunes on its own as the table grows.
+    for index in 0..10000 {
+        new_symbol(format!("unused{}", index));
+    }
+    assert!(symbol_count() < 1000);
+}